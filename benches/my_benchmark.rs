@@ -1,11 +1,13 @@
 use bytemuck::{Pod, Zeroable};
-use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
 use std::hint::black_box;
 use compression_experiments::*;
+
+/*
+use criterion::Throughput;
 use criterion::measurement::{Measurement, ValueFormatter};
 use std::fmt;
 
-/*
 #[derive(Clone, Copy, Debug)]
 pub struct CompressedBytes;
 
@@ -98,15 +100,15 @@ impl ValueFormatter for BytesFormatter {
 fn compress_into_void<C: Compressor<Input = T>, T: Pod + Zeroable + Sync + Send>(data: &[T]) -> u64 {
     let compressor = C::new();
     let mut output = Vec::<u8>::with_capacity(10000000);
-    compressor.compress(black_box(&data), &mut output);
-    assert!(output.len() > 0);
+    compressor.compress(black_box(data), &mut output);
+    assert!(!output.is_empty());
     output.len() as u64
 }
 
 fn decompress_into_void<C: Compressor<Input = T>, T: Pod + Zeroable + Sync + Send>(compressed: &[u8]) {
     let compressor = C::new();
     let mut output = Vec::<T>::with_capacity(10000000);
-    compressor.decompress(black_box(&compressed), &mut output);
+    compressor.decompress(black_box(compressed), &mut output);
 }
 
 /*
@@ -120,7 +122,7 @@ fn criterion_benchmark_sizes(c: &mut Criterion<CompressedBytes>) {
 
     let mut cgroup = c.benchmark_group("compress u64 repeated sizes");
     for size in [JUMP, 2 * JUMP, 4 * JUMP, 8 * JUMP, 16 * JUMP, 32 * JUMP, 64 * JUMP].iter() {
-        let data = std::iter::repeat_n(67_67_420u64 + 0xdef_baccu64, *size).collect::<Vec<_>>();
+        let data = std::iter::repeat_n(6_767_420u64 + 0xdef_baccu64, *size).collect::<Vec<_>>();
 
         cgroup.bench_with_input(BenchmarkId::new("COMPRESS RLE", size), size, |b, &size| {
             b.iter_custom(|_| compress_into_void::<RLE<u64>, u64>(black_box(&data)));
@@ -148,21 +150,21 @@ fn criterion_benchmark_times(c: &mut Criterion) {
 
     let mut cgroup = c.benchmark_group("compress u64 repeated");
     for size in [JUMP, 2 * JUMP, 4 * JUMP, 8 * JUMP, 16 * JUMP, 32 * JUMP, 64 * JUMP].iter() {
-        let data = std::iter::repeat_n(67_67_420u64 + 0xdef_baccu64, *size).collect::<Vec<_>>();
+        let data = std::iter::repeat_n(6_767_420u64 + 0xdef_baccu64, *size).collect::<Vec<_>>();
 
-        cgroup.bench_with_input(BenchmarkId::new("COMPRESS RLE", size), size, |b, &size| {
+        cgroup.bench_with_input(BenchmarkId::new("COMPRESS RLE", size), size, |b, _| {
             b.iter(|| compress_into_void::<RLE<u64>, u64>(black_box(&data)));
         });
 
-        cgroup.bench_with_input(BenchmarkId::new("COMPRESS VRLE", size), size, |b, &size| {
+        cgroup.bench_with_input(BenchmarkId::new("COMPRESS VRLE", size), size, |b, _| {
             b.iter(|| compress_into_void::<VRLE<u64>, u64>(black_box(&data)));
         });
 
-        cgroup.bench_with_input(BenchmarkId::new("COMPRESS PARCHUNKED RLE", size), size, |b, &size| {
+        cgroup.bench_with_input(BenchmarkId::new("COMPRESS PARCHUNKED RLE", size), size, |b, _| {
             b.iter(|| compress_into_void::<ParChunked<RLE<u64>>, u64>(black_box(&data)));
         });
 
-        cgroup.bench_with_input(BenchmarkId::new("COMPRESS PARCHUNKED VRLE", size), size, |b, &size| {
+        cgroup.bench_with_input(BenchmarkId::new("COMPRESS PARCHUNKED VRLE", size), size, |b, _| {
             b.iter(|| compress_into_void::<ParChunked<VRLE<u64>>, u64>(black_box(&data)));
         });
     }
@@ -170,9 +172,9 @@ fn criterion_benchmark_times(c: &mut Criterion) {
 
     let mut dgroup = c.benchmark_group("decompress u64 repeated");
     for size in [JUMP, 2 * JUMP, 4 * JUMP, 8 * JUMP, 16 * JUMP, 32 * JUMP, 64 * JUMP].iter() {
-        let data = std::iter::repeat_n(67_67_420u64 + 0xdef_baccu64, *size).collect::<Vec<_>>();
+        let data = std::iter::repeat_n(6_767_420u64 + 0xdef_baccu64, *size).collect::<Vec<_>>();
 
-        dgroup.bench_with_input(BenchmarkId::new("DECOMPRESS RLE", size), size, |b, &size| {
+        dgroup.bench_with_input(BenchmarkId::new("DECOMPRESS RLE", size), size, |b, _| {
             let compressor = RLE::<u64>::new();
             let mut compressed = Vec::<u8>::with_capacity(10000000);
             compressor.compress(&data, &mut compressed);
@@ -181,7 +183,7 @@ fn criterion_benchmark_times(c: &mut Criterion) {
             b.iter(|| decompress_into_void::<RLE<u64>, u64>(black_box(&compressed)));
         });
 
-        dgroup.bench_with_input(BenchmarkId::new("DECOMPRESS VRLE", size), size, |b, &size| {
+        dgroup.bench_with_input(BenchmarkId::new("DECOMPRESS VRLE", size), size, |b, _| {
             let compressor = VRLE::<u64>::new();
             let mut compressed = Vec::<u8>::with_capacity(10000000);
             compressor.compress(&data, &mut compressed);
@@ -189,7 +191,7 @@ fn criterion_benchmark_times(c: &mut Criterion) {
             b.iter(|| decompress_into_void::<VRLE<u64>, u64>(black_box(&compressed)));
         });
 
-        dgroup.bench_with_input(BenchmarkId::new("DECOMPRESS PARCHUNKED RLE", size), size, |b, &size| {
+        dgroup.bench_with_input(BenchmarkId::new("DECOMPRESS PARCHUNKED RLE", size), size, |b, _| {
             let compressor = ParChunked::<RLE<u64>>::new();
             let mut compressed = Vec::<u8>::with_capacity(10000000);
             compressor.compress(&data, &mut compressed);
@@ -197,7 +199,7 @@ fn criterion_benchmark_times(c: &mut Criterion) {
             b.iter(|| decompress_into_void::<ParChunked<RLE<u64>>, u64>(black_box(&compressed)));
         });
 
-        dgroup.bench_with_input(BenchmarkId::new("DECOMPRESS PARCHUNKED VRLE", size), size, |b, &size| {
+        dgroup.bench_with_input(BenchmarkId::new("DECOMPRESS PARCHUNKED VRLE", size), size, |b, _| {
             let compressor = ParChunked::<VRLE<u64>>::new();
             let mut compressed = Vec::<u8>::with_capacity(10000000);
             compressor.compress(&data, &mut compressed);
@@ -207,6 +209,84 @@ fn criterion_benchmark_times(c: &mut Criterion) {
     }
 }
 
+// run lengths as they come out of `split_runs` on our data: mostly short runs with a long tail
+fn run_lengths(size: usize) -> Vec<u64> {
+    let mut state = 0x9e37_79b9u32;
+    (0..size).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        // the number of trailing zeros is geometric, so every extra bit of length is half as likely
+        (1u64 << (state.trailing_zeros() % 20)) | ((state >> 24) as u64 % 4)
+    }).collect()
+}
+
+fn criterion_benchmark_counts(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode run lengths");
+    let counts = run_lengths(100_000);
+
+    for layout in CountLayout::ALL {
+        let mut encoded = Vec::<u8>::new();
+        layout.write_counts(&counts, &mut encoded);
+        println!("{layout:?}: {} bytes for {} run lengths", encoded.len(), counts.len());
+
+        group.bench_with_input(BenchmarkId::new("DECODE", format!("{layout:?}")), &encoded, |b, encoded| {
+            b.iter(|| layout.try_read_counts(black_box(encoded)).unwrap());
+        });
+    }
+    drop(group);
+
+    let mut group = c.benchmark_group("decompress vrle count layouts");
+    let data = run_lengths(20_000).into_iter().enumerate()
+        .flat_map(|(i, length)| std::iter::repeat_n(i as u32 % 7, length.min(64) as usize))
+        .collect::<Vec<_>>();
+
+    for layout in CountLayout::ALL {
+        let compressor = VRLE::<u32>::new().with_layout(layout);
+        let mut compressed = Vec::<u8>::new();
+        compressor.compress(&data, &mut compressed);
+        println!("VRLE {layout:?}: {} bytes for {} elements", compressed.len(), data.len());
+
+        group.bench_with_input(BenchmarkId::new("DECOMPRESS VRLE", format!("{layout:?}")), &compressed, |b, compressed| {
+            b.iter(|| decompress_into_void::<VRLE<u32>, u32>(black_box(compressed)));
+        });
+    }
+}
+
+fn hybrid_candidates() -> Hybrid<u32> {
+    Hybrid::<u32>::new()
+        .add::<RLE<u32>>()
+        .add::<VRLE<u32>>()
+        .add::<LZ<u32>>()
+        .add::<BitPack<u32>>()
+        .add::<Delta<u32, BitPack<u32>>>()
+        .add::<Huffman<u32>>()
+}
+
+fn criterion_benchmark_hybrid(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compress hybrid");
+    let data = run_lengths(20_000).into_iter().enumerate()
+        .flat_map(|(i, length)| std::iter::repeat_n(i as u32 % 1000, length.min(64) as usize))
+        .collect::<Vec<_>>();
+
+    let variants = [
+        ("SEQUENTIAL", hybrid_candidates().with_parallel(false)),
+        ("SEQUENTIAL BOUNDED", hybrid_candidates().with_parallel(false).with_size_bound(true)),
+        ("PARALLEL", hybrid_candidates()),
+        ("PARALLEL BOUNDED", hybrid_candidates().with_size_bound(true)),
+    ];
+
+    for (name, hybrid) in variants.iter() {
+        group.bench_with_input(BenchmarkId::new("COMPRESS HYBRID", name), &data, |b, data| {
+            b.iter(|| {
+                let mut compressed = Vec::<u8>::new();
+                hybrid.compress(black_box(data), &mut compressed);
+                compressed
+            });
+        });
+    }
+}
+
 /*
 criterion_group! {
     name = size_benches;
@@ -214,6 +294,6 @@ criterion_group! {
     targets = criterion_benchmark_sizes
 }
 */
-criterion_group!(time_benches, criterion_benchmark_times);
+criterion_group!(time_benches, criterion_benchmark_times, criterion_benchmark_counts, criterion_benchmark_hybrid);
 
 criterion_main!(time_benches);
\ No newline at end of file
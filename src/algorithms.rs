@@ -4,12 +4,36 @@ mod parallel_chunked;
 mod delta;
 mod hybrid;
 mod lookup;
+mod lz;
+mod bits;
+mod huffman;
+mod arithmetic;
+mod bitpack;
+mod gorilla;
+mod delta_of_delta;
+mod shuffle;
+mod columnar;
+mod segmented;
 mod common;
+mod count_codec;
+mod integer;
 
 pub use common::*;
+pub use count_codec::*;
+pub use bits::{BitReader, BitWriter};
+pub use integer::Integer;
 pub use run_length_encoding::RLE;
 pub use variable_run_length_encoding::VRLE;
 pub use parallel_chunked::ParChunked;
-pub use delta::Delta;
-pub use hybrid::Hybrid;
-pub use lookup::*;
\ No newline at end of file
+pub use delta::*;
+pub use hybrid::{Hybrid, Objective, Selection, SelectionReport, Trial};
+pub use lookup::*;
+pub use lz::LZ;
+pub use huffman::Huffman;
+pub use arithmetic::{Arithmetic, ContextModel, Order0, Order1};
+pub use bitpack::{BitPack, PFor};
+pub use gorilla::{Gorilla, GorillaElement};
+pub use delta_of_delta::*;
+pub use shuffle::*;
+pub use columnar::*;
+pub use segmented::Segmented;
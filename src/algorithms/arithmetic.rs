@@ -0,0 +1,231 @@
+use std::marker::PhantomData;
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
+
+// probabilities are 11 bit fixed point, adapting by 1/32 of the error after every bit
+const PROBABILITY_BITS: u32 = 11;
+const ADAPT_SHIFT: u32 = 5;
+const TOP: u32 = 1 << 24;
+
+// picks the set of adaptive probabilities a byte is coded with
+pub trait ContextModel: Send + Sync {
+    const CONTEXTS: usize;
+    const ALGORITHM: AlgorithmId;
+
+    fn context(previous: u8) -> usize;
+}
+
+// a single set of probabilities for every byte
+pub struct Order0;
+
+// the previous byte selects the probabilities
+pub struct Order1;
+
+impl ContextModel for Order0 {
+    const CONTEXTS: usize = 1;
+    const ALGORITHM: AlgorithmId = AlgorithmId::ArithmeticOrder0;
+
+    fn context(_previous: u8) -> usize {
+        0
+    }
+}
+
+impl ContextModel for Order1 {
+    const CONTEXTS: usize = 256;
+    const ALGORITHM: AlgorithmId = AlgorithmId::ArithmeticOrder1;
+
+    fn context(previous: u8) -> usize {
+        previous as usize
+    }
+}
+
+// adaptive binary range coder over the raw bytes of the elements
+// every byte is coded as 8 binary decisions down a bit tree, so each context holds 255 probabilities
+// layout: byte count, then the range coder output
+pub struct Arithmetic<T: Pod = u8, M: ContextModel = Order0> {
+    _phantom: PhantomData<(T, M)>,
+}
+
+fn probabilities<M: ContextModel>() -> Vec<u16> {
+    vec![1 << (PROBABILITY_BITS - 1); M::CONTEXTS * 256]
+}
+
+struct Encoder<'a> {
+    buffer: &'a mut Vec<u8>,
+    low: u64,
+    range: u32,
+    // the last byte below `low` that may still change through a carry, followed by `pending` 0xff bytes
+    cache: u8,
+    pending: u64,
+}
+
+impl<'a> Encoder<'a> {
+    fn new(buffer: &'a mut Vec<u8>) -> Self {
+        Self { buffer, low: 0, range: u32::MAX, cache: 0, pending: 1 }
+    }
+
+    fn encode_bit(&mut self, probability: &mut u16, bit: bool) {
+        let bound = (self.range >> PROBABILITY_BITS) * *probability as u32;
+
+        if bit {
+            self.low += bound as u64;
+            self.range -= bound;
+            *probability -= *probability >> ADAPT_SHIFT;
+        } else {
+            self.range = bound;
+            *probability += ((1 << PROBABILITY_BITS) - *probability) >> ADAPT_SHIFT;
+        }
+
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    fn shift_low(&mut self) {
+        if self.low < 0xff00_0000 || self.low >= 1 << 32 {
+            let carry = (self.low >> 32) as u8;
+            self.buffer.push(self.cache.wrapping_add(carry));
+            for _ in 1..self.pending {
+                self.buffer.push(0xffu8.wrapping_add(carry));
+            }
+
+            self.pending = 0;
+            self.cache = (self.low >> 24) as u8;
+        }
+
+        self.pending += 1;
+        self.low = (self.low & 0x00ff_ffff) << 8;
+    }
+
+    fn finish(mut self) {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+    }
+}
+
+struct Decoder<'a> {
+    buffer: &'a [u8],
+    index: usize,
+    code: u32,
+    range: u32,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(buffer: &'a [u8]) -> Result<Self, CompressionError> {
+        let mut decoder = Self { buffer, index: 0, code: 0, range: u32::MAX };
+        for _ in 0..5 {
+            decoder.code = (decoder.code << 8) | decoder.next_byte()? as u32;
+        }
+
+        Ok(decoder)
+    }
+
+    fn next_byte(&mut self) -> Result<u8, CompressionError> {
+        let byte = *self.buffer.get(self.index).ok_or(CompressionError::Truncated)?;
+        self.index += 1;
+        Ok(byte)
+    }
+
+    fn decode_bit(&mut self, probability: &mut u16) -> Result<bool, CompressionError> {
+        let bound = (self.range >> PROBABILITY_BITS) * *probability as u32;
+
+        let bit = if self.code < bound {
+            self.range = bound;
+            *probability += ((1 << PROBABILITY_BITS) - *probability) >> ADAPT_SHIFT;
+            false
+        } else {
+            self.code -= bound;
+            self.range -= bound;
+            *probability -= *probability >> ADAPT_SHIFT;
+            true
+        };
+
+        while self.range < TOP {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.next_byte()? as u32;
+        }
+
+        Ok(bit)
+    }
+}
+
+impl<T: Pod, M: ContextModel> Compressor for Arithmetic<T, M> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        let bytes = bytemuck::cast_slice::<T, u8>(uncompressed);
+        write_count_bytes(bytes.len() as u64, compressed);
+
+        if bytes.is_empty() {
+            return;
+        }
+
+        let mut probabilities = probabilities::<M>();
+        let mut encoder = Encoder::new(compressed);
+        let mut previous = 0u8;
+
+        for byte in bytes {
+            let tree = &mut probabilities[(M::context(previous) * 256)..][..256];
+            let mut node = 1;
+
+            for shift in (0..8).rev() {
+                let bit = (byte >> shift) & 1 == 1;
+                encoder.encode_bit(&mut tree[node], bit);
+                node = (node << 1) | bit as usize;
+            }
+
+            previous = *byte;
+        }
+
+        encoder.finish();
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let (count, bytes_read) = try_read_count_bytes(compressed)?;
+        if count % size_of::<T>() as u64 != 0 {
+            return Err(CompressionError::Truncated);
+        }
+
+        check_limit(count / size_of::<T>() as u64, limit)?;
+
+        if count == 0 {
+            return Ok(());
+        }
+
+        let mut probabilities = probabilities::<M>();
+        let mut decoder = Decoder::new(&compressed[bytes_read..])?;
+        let mut previous = 0u8;
+
+        // the coder can't produce much more than 8 bytes per input bit, so cap the up front allocation
+        let mut bytes = Vec::<u8>::with_capacity(count.min(compressed.len() as u64 * 64) as usize);
+        for _ in 0..count {
+            let tree = &mut probabilities[(M::context(previous) * 256)..][..256];
+            let mut node = 1;
+
+            for _ in 0..8 {
+                node = (node << 1) | decoder.decode_bit(&mut tree[node])? as usize;
+            }
+
+            previous = node as u8;
+            bytes.push(previous);
+        }
+
+        uncompressed.extend(bytes.chunks_exact(size_of::<T>()).map(bytemuck::pod_read_unaligned::<T>));
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(M::ALGORITHM)
+    }
+
+    fn new() -> Self {
+        Self {
+            _phantom: Default::default(),
+        }
+    }
+}
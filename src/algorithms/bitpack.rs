@@ -0,0 +1,299 @@
+use std::marker::PhantomData;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
+use crate::algorithms::integer::Integer;
+use crate::algorithms::bits::BitWriter;
+
+const DEFAULT_BLOCK_SIZE: usize = 128;
+
+// frame of reference: every block stores its minimum and packs `value - min` with just enough bits for the largest one
+// layout: element count, block size, then per block: minimum (count bytes), bit width (u8), packed bits (byte aligned)
+pub struct BitPack<T: Integer> {
+    pub block_size: usize,
+    _phantom: PhantomData<T>,
+}
+
+// patched frame of reference: like `BitPack`, but the bit width is picked so that rare outliers don't widen the
+// whole block, the bits that don't fit are stored as exceptions
+// per block: minimum, bit width, exception count, every exception as (index, high bits), packed bits
+pub struct PFor<T: Integer> {
+    pub block_size: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Integer> BitPack<T> {
+    pub fn new_with(block_size: usize) -> Self {
+        assert!(block_size > 0);
+        Self {
+            block_size,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<T: Integer> PFor<T> {
+    pub fn new_with(block_size: usize) -> Self {
+        assert!(block_size > 0);
+        Self {
+            block_size,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+fn bit_width(value: u64) -> u32 {
+    u64::BITS - value.leading_zeros()
+}
+
+fn low_bits(value: u64, width: u32) -> u64 {
+    if width == 64 { value } else { value & ((1u64 << width) - 1) }
+}
+
+// the block's minimum (as an ordered value) and every element's offset from it
+fn offsets<T: Integer>(block: &[T], offsets: &mut Vec<u64>) -> u64 {
+    let reference = block.iter().map(|value| value.to_ordered()).min().unwrap_or_default();
+    offsets.clear();
+    offsets.extend(block.iter().map(|value| value.to_ordered() - reference));
+    reference
+}
+
+// exceptions cost roughly an index byte, a length byte and their remaining bits
+fn patched_width(offsets: &[u64]) -> u32 {
+    let mut widths = [0usize; 65];
+    for offset in offsets {
+        widths[bit_width(*offset) as usize] += 1;
+    }
+
+    let max_width = widths.iter().rposition(|count| *count > 0).unwrap_or_default() as u32;
+    let mut exceptions = 0;
+    let mut best = (offsets.len() as u64 * max_width as u64, max_width);
+
+    for width in (0..max_width).rev() {
+        exceptions += widths[width as usize + 1];
+        let cost = offsets.len() as u64 * width as u64 + exceptions as u64 * (16 + (max_width - width) as u64);
+        if cost < best.0 {
+            best = (cost, width);
+        }
+    }
+
+    best.1
+}
+
+fn pack(offsets: &[u64], width: u32, compressed: &mut Vec<u8>) {
+    let mut writer = BitWriter::new(compressed);
+    for offset in offsets {
+        writer.write_bits(low_bits(*offset, width), width);
+    }
+
+    writer.finish();
+}
+
+// reads every value through a 16 byte big endian window, so a single shift extracts it whatever its alignment
+fn unpack(packed: &[u8], width: u32, count: usize, offsets: &mut Vec<u64>) {
+    offsets.clear();
+
+    if width == 0 {
+        offsets.resize(count, 0);
+        return;
+    }
+
+    offsets.extend((0..count).map(|i| {
+        let bit = i * width as usize;
+        let start = bit / 8;
+        let available = (packed.len() - start).min(16);
+
+        let mut window = [0u8; 16];
+        window[..available].copy_from_slice(&packed[start..(start + available)]);
+        let word = u128::from_be_bytes(window);
+        low_bits((word >> (128 - (bit % 8) as u32 - width)) as u64, width)
+    }));
+}
+
+fn read_block_header<T: Integer>(compressed: &[u8], index: &mut usize) -> Result<(u64, u32), CompressionError> {
+    let reference = try_read_count(compressed, index)?;
+    let width = *compressed.get(*index).ok_or(CompressionError::Truncated)? as u32;
+    *index += 1;
+
+    if width > T::BITS {
+        return Err(CompressionError::InvalidHeader("bit width larger than the element"));
+    }
+
+    Ok((reference, width))
+}
+
+fn read_packed<'a>(compressed: &'a [u8], index: &mut usize, width: u32, count: usize) -> Result<&'a [u8], CompressionError> {
+    let length = count.checked_mul(width as usize).ok_or(CompressionError::Truncated)?.div_ceil(8);
+    let end = index.checked_add(length).ok_or(CompressionError::Truncated)?;
+    let packed = compressed.get(*index..end).ok_or(CompressionError::Truncated)?;
+    *index += length;
+    Ok(packed)
+}
+
+fn write_header(count: usize, block_size: usize, compressed: &mut Vec<u8>) {
+    write_count_bytes(count as u64, compressed);
+    write_count_bytes(block_size as u64, compressed);
+}
+
+// a block of width 0 takes no packed bytes at all, so nothing but `limit` keeps the element count in check
+fn read_header(compressed: &[u8], index: &mut usize, limit: usize) -> Result<(u64, u64), CompressionError> {
+    let count = try_read_count(compressed, index)?;
+    let block_size = try_read_count(compressed, index)?;
+    if block_size == 0 && count > 0 {
+        return Err(CompressionError::InvalidHeader("block size is zero"));
+    }
+
+    check_limit(count, limit)?;
+
+    Ok((count, block_size))
+}
+
+impl<T: Integer> Compressor for BitPack<T> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        self.compress_bounded(uncompressed, compressed, usize::MAX);
+    }
+
+    fn compress_bounded(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        let start = compressed.len();
+        write_header(uncompressed.len(), self.block_size, compressed);
+        let mut block_offsets = Vec::with_capacity(self.block_size);
+
+        for block in uncompressed.chunks(self.block_size) {
+            let reference = offsets(block, &mut block_offsets);
+            let width = block_offsets.iter().map(|offset| bit_width(*offset)).max().unwrap_or_default();
+
+            write_count_bytes(reference, compressed);
+            compressed.push(width as u8);
+            pack(&block_offsets, width, compressed);
+
+            if compressed.len() - start > limit {
+                return false;
+            }
+        }
+
+        compressed.len() - start <= limit
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut index = 0;
+        let (count, block_size) = read_header(compressed, &mut index, limit)?;
+        let mut remaining = count;
+        let mut block_offsets = Vec::new();
+
+        while remaining > 0 {
+            let length = remaining.min(block_size) as usize;
+            let (reference, width) = read_block_header::<T>(compressed, &mut index)?;
+            let packed = read_packed(compressed, &mut index, width, length)?;
+
+            unpack(packed, width, length, &mut block_offsets);
+            uncompressed.extend(block_offsets.iter().map(|offset| T::from_ordered(reference.wrapping_add(*offset))));
+            remaining -= length as u64;
+        }
+
+        if index != compressed.len() {
+            return Err(CompressionError::TrailingBytes(compressed.len() - index));
+        }
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::BitPack)
+    }
+
+    fn new() -> Self {
+        Self::new_with(DEFAULT_BLOCK_SIZE)
+    }
+}
+
+impl<T: Integer> Compressor for PFor<T> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        self.compress_bounded(uncompressed, compressed, usize::MAX);
+    }
+
+    fn compress_bounded(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        let start = compressed.len();
+        write_header(uncompressed.len(), self.block_size, compressed);
+        let mut block_offsets = Vec::with_capacity(self.block_size);
+
+        for block in uncompressed.chunks(self.block_size) {
+            let reference = offsets(block, &mut block_offsets);
+            let width = patched_width(&block_offsets);
+
+            write_count_bytes(reference, compressed);
+            compressed.push(width as u8);
+
+            let exceptions = block_offsets.iter().enumerate()
+                .filter(|(_, offset)| bit_width(**offset) > width)
+                .collect::<Vec<_>>();
+
+            write_count_bytes(exceptions.len() as u64, compressed);
+            for (position, offset) in exceptions {
+                write_count_bytes(position as u64, compressed);
+                write_count_bytes(*offset >> width, compressed);
+            }
+
+            pack(&block_offsets, width, compressed);
+
+            if compressed.len() - start > limit {
+                return false;
+            }
+        }
+
+        compressed.len() - start <= limit
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut index = 0;
+        let (count, block_size) = read_header(compressed, &mut index, limit)?;
+        let mut remaining = count;
+        let mut block_offsets = Vec::new();
+        let mut exceptions = Vec::new();
+
+        while remaining > 0 {
+            let length = remaining.min(block_size) as usize;
+            let (reference, width) = read_block_header::<T>(compressed, &mut index)?;
+
+            exceptions.clear();
+            for _ in 0..try_read_count(compressed, &mut index)? {
+                let position = try_read_count(compressed, &mut index)?;
+                let high = try_read_count(compressed, &mut index)?;
+                if position >= length as u64 {
+                    return Err(CompressionError::InvalidHeader("exception outside of its block"));
+                }
+
+                exceptions.push((position as usize, high));
+            }
+
+            let packed = read_packed(compressed, &mut index, width, length)?;
+            unpack(packed, width, length, &mut block_offsets);
+
+            // a width of 64 leaves no room for high bits, so there can't be any exceptions to patch in
+            for (position, high) in exceptions.iter() {
+                block_offsets[*position] |= high.checked_shl(width).unwrap_or_default();
+            }
+
+            uncompressed.extend(block_offsets.iter().map(|offset| T::from_ordered(reference.wrapping_add(*offset))));
+            remaining -= length as u64;
+        }
+
+        if index != compressed.len() {
+            return Err(CompressionError::TrailingBytes(compressed.len() - index));
+        }
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::PFor)
+    }
+
+    fn new() -> Self {
+        Self::new_with(DEFAULT_BLOCK_SIZE)
+    }
+}
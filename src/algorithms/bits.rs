@@ -0,0 +1,213 @@
+use crate::error::CompressionError;
+
+// writes bits MSB first, the last byte is padded with zeros
+pub struct BitWriter<'a> {
+    buffer: &'a mut Vec<u8>,
+    accumulator: u64,
+    bits: u32,
+}
+
+impl<'a> BitWriter<'a> {
+    pub fn new(buffer: &'a mut Vec<u8>) -> Self {
+        Self { buffer, accumulator: 0, bits: 0 }
+    }
+
+    // writes the lowest `count` bits of `value`
+    pub fn write_bits(&mut self, value: u64, count: u32) {
+        debug_assert!(count <= 64);
+
+        if count > 32 {
+            self.write_bits(value >> 32, count - 32);
+            self.write_bits(value & 0xffff_ffff, 32);
+            return;
+        }
+
+        let mask = (1u64 << count) - 1;
+        self.accumulator = (self.accumulator << count) | (value & mask);
+        self.bits += count;
+
+        while self.bits >= 8 {
+            self.bits -= 8;
+            self.buffer.push((self.accumulator >> self.bits) as u8);
+        }
+
+        self.accumulator &= (1u64 << self.bits) - 1;
+    }
+
+    pub fn write_bit(&mut self, bit: bool) {
+        self.write_bits(bit as u64, 1);
+    }
+
+    // `value` ones followed by a zero
+    pub fn write_unary(&mut self, mut value: u64) {
+        while value >= 32 {
+            self.write_bits(u32::MAX as u64, 32);
+            value -= 32;
+        }
+
+        self.write_bits(((1u64 << value) - 1) << 1, value as u32 + 1);
+    }
+
+    // Elias gamma: as many zeros as `value` has bits after its leading one, then `value` itself
+    // only defined for values of at least 1
+    pub fn write_gamma(&mut self, value: u64) {
+        assert!(value > 0);
+        let width = u64::BITS - value.leading_zeros();
+        self.write_bits(0, width - 1);
+        self.write_bits(value, width);
+    }
+
+    // Golomb-Rice: the quotient `value >> k` in unary, then the `k` low bits
+    pub fn write_rice(&mut self, value: u64, k: u32) {
+        debug_assert!(k < 64);
+        self.write_unary(value >> k);
+        self.write_bits(value, k);
+    }
+
+    // pads with zeros up to the next byte boundary, later writes start on a fresh byte
+    pub fn align(&mut self) {
+        if self.bits > 0 {
+            self.write_bits(0, 8 - self.bits);
+        }
+    }
+
+    pub fn finish(mut self) {
+        self.align();
+    }
+}
+
+pub struct BitReader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, position: 0 }
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool, CompressionError> {
+        let byte = self.buffer.get(self.position / 8).ok_or(CompressionError::Truncated)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Ok(bit == 1)
+    }
+
+    pub fn read_bits(&mut self, count: u32) -> Result<u64, CompressionError> {
+        debug_assert!(count <= 64);
+        let mut value = 0u64;
+        let mut remaining = count;
+
+        while remaining > 0 {
+            let byte = *self.buffer.get(self.position / 8).ok_or(CompressionError::Truncated)?;
+            let available = 8 - (self.position % 8) as u32;
+            let taken = available.min(remaining);
+            let bits = (byte as u64 >> (available - taken)) & ((1u64 << taken) - 1);
+
+            value = (value << taken) | bits;
+            self.position += taken as usize;
+            remaining -= taken;
+        }
+
+        Ok(value)
+    }
+
+    pub fn read_unary(&mut self) -> Result<u64, CompressionError> {
+        let mut value = 0u64;
+        while self.read_bit()? {
+            value += 1;
+        }
+
+        Ok(value)
+    }
+
+    pub fn read_gamma(&mut self) -> Result<u64, CompressionError> {
+        let mut zeros = 0;
+        while !self.read_bit()? {
+            zeros += 1;
+
+            if zeros >= u64::BITS {
+                return Err(CompressionError::InvalidCode);
+            }
+        }
+
+        Ok((1 << zeros) | self.read_bits(zeros)?)
+    }
+
+    pub fn read_rice(&mut self, k: u32) -> Result<u64, CompressionError> {
+        let quotient = self.read_unary()?;
+        let remainder = self.read_bits(k)?;
+        quotient.checked_shl(k).filter(|value| value >> k == quotient)
+            .map(|value| value | remainder)
+            .ok_or(CompressionError::InvalidCode)
+    }
+
+    // skips the padding up to the next byte boundary
+    pub fn align(&mut self) {
+        self.position = self.position.next_multiple_of(8);
+    }
+
+    // whole bytes touched so far, including a partially read one
+    pub fn bytes_consumed(&self) -> usize {
+        self.position.div_ceil(8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_round_trip() {
+        let values = [1u64, 2, 3, 7, 8, 100, 1 << 40, u64::MAX];
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+
+        for value in values {
+            writer.write_gamma(value);
+            writer.write_rice(value % 1000, 3);
+            writer.write_unary(value % 70);
+            writer.write_bits(value, 64);
+            writer.write_bit(value % 2 == 0);
+        }
+
+        writer.align();
+        writer.write_bits(0b101, 3);
+        writer.finish();
+
+        let mut reader = BitReader::new(&buffer);
+        for value in values {
+            assert_eq!(reader.read_gamma().unwrap(), value);
+            assert_eq!(reader.read_rice(3).unwrap(), value % 1000);
+            assert_eq!(reader.read_unary().unwrap(), value % 70);
+            assert_eq!(reader.read_bits(64).unwrap(), value);
+            assert_eq!(reader.read_bit().unwrap(), value % 2 == 0);
+        }
+
+        reader.align();
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+        assert_eq!(reader.bytes_consumed(), buffer.len());
+    }
+
+    #[test]
+    fn test_code_layouts() {
+        let mut buffer = Vec::new();
+        let mut writer = BitWriter::new(&mut buffer);
+        writer.write_gamma(5);
+        writer.write_unary(2);
+        writer.write_rice(9, 2);
+        writer.finish();
+
+        // 00101 110 110 01, padded
+        assert_eq!(buffer, [0b0010_1110, 0b1100_1000]);
+    }
+
+    #[test]
+    fn test_reading_past_the_end() {
+        let mut reader = BitReader::new(&[0b1111_1111]);
+        assert_eq!(reader.read_unary(), Err(CompressionError::Truncated));
+
+        let mut reader = BitReader::new(&[0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(reader.read_gamma(), Err(CompressionError::InvalidCode));
+    }
+}
@@ -0,0 +1,221 @@
+use std::{marker::PhantomData, ops::Range};
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor, DynCompressor, build};
+use crate::algorithms::common::*;
+use crate::algorithms::integer::Integer;
+
+// byte range of every field within a struct, in declaration order
+// implement it with `columns!(Point { x, y })`
+pub trait Columns: Pod {
+    fn columns() -> Vec<Range<usize>>;
+}
+
+// lets `columns!` get the size of a field without naming its type
+pub fn field_size<T, F>(_field: fn(&T) -> &F) -> usize {
+    size_of::<F>()
+}
+
+#[macro_export]
+macro_rules! columns {
+    ($t:ty { $($field:ident),+ $(,)? }) => {
+        impl $crate::Columns for $t {
+            fn columns() -> Vec<std::ops::Range<usize>> {
+                vec![$({
+                    let offset = std::mem::offset_of!($t, $field);
+                    offset..(offset + $crate::field_size(|value: &$t| &value.$field))
+                }),+]
+            }
+        }
+    };
+}
+
+// 1, 2, 4 and 8 byte fields are compressed as integers of that width, any other field as its raw bytes
+enum ColumnStack {
+    U8(DynCompressor<u8>),
+    U16(DynCompressor<u16>),
+    U32(DynCompressor<u32>),
+    U64(DynCompressor<u64>),
+}
+
+fn compress_as<U: Integer>(compressor: &DynCompressor<U>, bytes: &[u8], compressed: &mut Vec<u8>) {
+    let values = bytes.chunks_exact(size_of::<U>()).map(bytemuck::pod_read_unaligned::<U>).collect::<Vec<_>>();
+    compressor.compress(&values, compressed);
+}
+
+fn decompress_as<U: Integer>(compressor: &DynCompressor<U>, compressed: &[u8], bytes: &mut Vec<u8>, limit: usize) -> Result<(), CompressionError> {
+    let mut values = Vec::<U>::new();
+    compressor.try_decompress_bounded(compressed, &mut values, limit / size_of::<U>())?;
+    bytes.extend_from_slice(bytemuck::cast_slice(&values));
+    Ok(())
+}
+
+impl ColumnStack {
+    fn build(width: usize, descriptor: &Descriptor) -> Result<Self, CompressionError> {
+        Ok(match width {
+            2 => Self::U16(build::<u16>(descriptor)?),
+            4 => Self::U32(build::<u32>(descriptor)?),
+            8 => Self::U64(build::<u64>(descriptor)?),
+            _ => Self::U8(build::<u8>(descriptor)?),
+        })
+    }
+
+    fn compress(&self, bytes: &[u8], compressed: &mut Vec<u8>) {
+        match self {
+            Self::U8(compressor) => compressor.compress(bytes, compressed),
+            Self::U16(compressor) => compress_as(compressor, bytes, compressed),
+            Self::U32(compressor) => compress_as(compressor, bytes, compressed),
+            Self::U64(compressor) => compress_as(compressor, bytes, compressed),
+        }
+    }
+
+    // `limit` is in bytes
+    fn try_decompress(&self, compressed: &[u8], bytes: &mut Vec<u8>, limit: usize) -> Result<(), CompressionError> {
+        match self {
+            Self::U8(compressor) => compressor.try_decompress_bounded(compressed, bytes, limit),
+            Self::U16(compressor) => decompress_as(compressor, compressed, bytes, limit),
+            Self::U32(compressor) => decompress_as(compressor, compressed, bytes, limit),
+            Self::U64(compressor) => decompress_as(compressor, compressed, bytes, limit),
+        }
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        match self {
+            Self::U8(compressor) => compressor.descriptor(),
+            Self::U16(compressor) => compressor.descriptor(),
+            Self::U32(compressor) => compressor.descriptor(),
+            Self::U64(compressor) => compressor.descriptor(),
+        }
+    }
+}
+
+// splits an array of structs into one column per field and compresses every column with its own stack
+// layout: for every column, its compressed length and then its compressed bytes
+pub struct Columnar<T: Columns> {
+    columns: Vec<(Range<usize>, ColumnStack)>,
+    _phantom: PhantomData<T>,
+}
+
+// what every column gets unless told otherwise
+pub fn default_column_stack() -> Descriptor {
+    let naive = || Descriptor::leaf(AlgorithmId::Naive);
+    let vrle = || Descriptor::node(AlgorithmId::VRLE, vec![naive(), naive()]);
+
+    Descriptor::node(AlgorithmId::Hybrid, vec![
+        vrle(),
+        Descriptor::node(AlgorithmId::Delta, vec![vrle()]),
+        Descriptor::leaf(AlgorithmId::BitPack),
+        Descriptor::leaf(AlgorithmId::LZ),
+    ])
+}
+
+impl<T: Columns> Columnar<T> {
+    // one stack per column, in the order of `T::columns()`
+    pub fn new_with(stacks: &[Descriptor]) -> Result<Self, CompressionError> {
+        let ranges = T::columns();
+
+        // every byte of the struct has to end up in exactly one column
+        let mut covered = vec![false; size_of::<T>()];
+        for range in ranges.iter() {
+            for byte in range.clone() {
+                assert!(!covered[byte], "columns of a struct overlap");
+                covered[byte] = true;
+            }
+        }
+        assert!(covered.iter().all(|byte| *byte), "columns don't cover the whole struct");
+
+        if stacks.len() != ranges.len() {
+            return Err(CompressionError::InvalidHeader("wrong number of column stacks"));
+        }
+
+        let columns = ranges.into_iter().zip(stacks)
+            .map(|(range, stack)| Ok((range.clone(), ColumnStack::build(range.len(), stack)?)))
+            .collect::<Result<Vec<_>, CompressionError>>()?;
+
+        Ok(Self { columns, _phantom: Default::default() })
+    }
+
+    // rebuilds a columnar compressor from its own descriptor
+    pub fn from_descriptor(descriptor: &Descriptor) -> Result<Self, CompressionError> {
+        if descriptor.algorithm != AlgorithmId::Columnar {
+            return Err(CompressionError::UnsupportedAlgorithm(descriptor.algorithm as u8));
+        }
+
+        Self::new_with(&descriptor.children)
+    }
+}
+
+impl<T: Columns> Compressor for Columnar<T> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        let rows = bytemuck::cast_slice::<T, u8>(uncompressed);
+        let mut column = Vec::<u8>::new();
+        let mut column_compressed = Vec::<u8>::new();
+
+        for (range, stack) in self.columns.iter() {
+            column.clear();
+            for row in rows.chunks_exact(size_of::<T>()) {
+                column.extend_from_slice(&row[range.clone()]);
+            }
+
+            column_compressed.clear();
+            stack.compress(&column, &mut column_compressed);
+            write_count_bytes(column_compressed.len() as u64, compressed);
+            compressed.extend_from_slice(&column_compressed);
+        }
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut index = 0;
+        let mut rows: Option<Vec<u8>> = None;
+        let mut row_count = 0;
+        let mut column = Vec::<u8>::new();
+
+        for (range, stack) in self.columns.iter() {
+            let length = try_read_count(compressed, &mut index)?;
+            let end = usize::try_from(length).ok().and_then(|length| index.checked_add(length)).ok_or(CompressionError::Truncated)?;
+            let section = compressed.get(index..end).ok_or(CompressionError::Truncated)?;
+            index = end;
+
+            // once the first column is decoded the number of rows is known
+            let row_limit = if rows.is_some() { row_count } else { limit };
+            column.clear();
+            stack.try_decompress(section, &mut column, row_limit.saturating_mul(range.len()))?;
+            if !column.len().is_multiple_of(range.len()) {
+                return Err(CompressionError::Truncated);
+            }
+
+            // the first column decides how many rows there are, the others have to agree
+            let rows = rows.get_or_insert_with(|| {
+                row_count = column.len() / range.len();
+                vec![0u8; row_count * size_of::<T>()]
+            });
+
+            if column.len() / range.len() != row_count {
+                return Err(CompressionError::LengthMismatch { expected: row_count as u64, actual: (column.len() / range.len()) as u64 });
+            }
+
+            for (row, field) in rows.chunks_exact_mut(size_of::<T>()).zip(column.chunks_exact(range.len())) {
+                row[range.clone()].copy_from_slice(field);
+            }
+        }
+
+        if index != compressed.len() {
+            return Err(CompressionError::TrailingBytes(compressed.len() - index));
+        }
+
+        uncompressed.extend(rows.unwrap_or_default().chunks_exact(size_of::<T>()).map(bytemuck::pod_read_unaligned::<T>));
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::Columnar, self.columns.iter().map(|(_, stack)| stack.descriptor()).collect())
+    }
+
+    fn new() -> Self {
+        let stacks = vec![default_column_stack(); T::columns().len()];
+        Self::new_with(&stacks).expect("the default column stack builds for every width")
+    }
+}
@@ -1,25 +1,32 @@
+use bytemuck::Pod;
+use crate::compressor::{Compressor, check_limit};
+use crate::error::CompressionError;
+
 
 const MAX_REPRS: [u64; 3] = [u8::MAX as u64, u16::MAX as u64, u32::MAX as u64];
 
+// the total length of `counts` runs, if it fits in `limit`
+pub fn check_run_total(counts: &[u64], limit: usize) -> Result<u64, CompressionError> {
+    let total = counts.iter().try_fold(0u64, |total, count| total.checked_add(*count)).unwrap_or(u64::MAX);
+    check_limit(total, limit)?;
+    Ok(total)
+}
+
+// every header and count is written in little-endian so buffers can be shared between hosts
+// element values are copied as is, so they keep the byte order of the machine that wrote them
 pub fn write_count_bytes(count: u64, buffer: &mut Vec<u8>) {
     let mode = get_mode(count);
 
     buffer.push(mode as u8);
-
-    match mode {
-        0 => buffer.push(count as u8),
-        1 => buffer.extend_from_slice(&u16::to_ne_bytes(count as u16)),
-        2 => buffer.extend_from_slice(&u32::to_ne_bytes(count as u32)),
-        _ => buffer.extend_from_slice(&u64::to_ne_bytes(count)),
-    };
+    write_count_bytes_with_mode(count, mode, buffer);
 }
 
 pub fn write_count_bytes_with_mode(count: u64, mode: usize, buffer: &mut Vec<u8>) {
     match mode {
         0 => buffer.push(count as u8),
-        1 => buffer.extend_from_slice(&u16::to_ne_bytes(count as u16)),
-        2 => buffer.extend_from_slice(&u32::to_ne_bytes(count as u32)),
-        _ => buffer.extend_from_slice(&u64::to_ne_bytes(count)),
+        1 => buffer.extend_from_slice(&u16::to_le_bytes(count as u16)),
+        2 => buffer.extend_from_slice(&u32::to_le_bytes(count as u32)),
+        _ => buffer.extend_from_slice(&u64::to_le_bytes(count)),
     };
 }
 
@@ -33,22 +40,98 @@ pub fn get_mode(count: u64) -> usize {
 }
 
 pub fn read_count_bytes(buffer: &[u8]) -> (u64, usize) {
-    let mode = buffer[0] as usize;
-    let count = match mode {
-        0 => buffer[1] as u64,
-        1 => u16::from_ne_bytes([buffer[1], buffer[2]]) as u64,
-        2 => u32::from_ne_bytes([buffer[1], buffer[2], buffer[3], buffer[4]]) as u64,
-        _ => u64::from_ne_bytes([buffer[1], buffer[2], buffer[3], buffer[4], buffer[5], buffer[6], buffer[7], buffer[8]]),
-    };
+    match try_read_count_bytes(buffer) {
+        Ok(read) => read,
+        Err(err) => panic!("failed to read count: {err}"),
+    }
+}
+
+pub fn try_read_count_bytes(buffer: &[u8]) -> Result<(u64, usize), CompressionError> {
+    let mode = *buffer.first().ok_or(CompressionError::Truncated)?;
+    let (count, bytes_read) = try_read_count_bytes_with_mode(&buffer[1..], mode as usize)?;
+    Ok((count, bytes_read + 1))
+}
+
+// reads a count at the given index and moves the index past it
+pub fn try_read_count(buffer: &[u8], index: &mut usize) -> Result<u64, CompressionError> {
+    let (count, bytes_read) = try_read_count_bytes(buffer.get(*index..).ok_or(CompressionError::Truncated)?)?;
+    *index += bytes_read;
+    Ok(count)
+}
 
+pub fn try_read_count_bytes_with_mode(buffer: &[u8], mode: usize) -> Result<(u64, usize), CompressionError> {
     let bytes_read = match mode {
-        0 => 2,
-        1 => 3,
-        2 => 5,
-        _ => 9,
+        0 => 1,
+        1 => 2,
+        2 => 4,
+        3 => 8,
+        _ => return Err(CompressionError::UnknownMode(mode as u8)),
     };
 
-    (count, bytes_read)
+    let bytes = buffer.get(..bytes_read).ok_or(CompressionError::Truncated)?;
+    // zero padding the high bytes is enough since the count is little-endian
+    let mut copy = [0u8; 8];
+    copy[..bytes_read].copy_from_slice(bytes);
+    Ok((u64::from_le_bytes(copy), bytes_read))
+}
+
+// reads a fixed width little-endian u64 at the given index and moves the index past it
+pub fn try_read_u64(buffer: &[u8], index: &mut usize) -> Result<u64, CompressionError> {
+    try_read_value::<[u8; 8]>(buffer, index).map(u64::from_le_bytes)
+}
+
+// reads a single unaligned value at the given index and moves the index past it
+pub fn try_read_value<T: Pod>(buffer: &[u8], index: &mut usize) -> Result<T, CompressionError> {
+    let end = index.checked_add(size_of::<T>()).ok_or(CompressionError::Truncated)?;
+    let bytes = buffer.get(*index..end).ok_or(CompressionError::Truncated)?;
+    *index = end;
+    Ok(bytemuck::pod_read_unaligned(bytes))
+}
+
+// splits the input into runs of equal elements, returning the length of every run and its value
+pub fn split_runs<T: Pod + PartialEq>(uncompressed: &[T]) -> (Vec<u64>, Vec<T>) {
+    let mut counts = Vec::<u64>::new();
+    let mut values = Vec::<T>::new();
+
+    for x in uncompressed {
+        if values.last() == Some(x) {
+            *counts.last_mut().unwrap() += 1;
+        } else {
+            counts.push(1);
+            values.push(*x);
+        }
+    }
+
+    (counts, values)
+}
+
+// the encoded run counts go through `count_compressor` and are prefixed by their compressed length
+// the run values go through `value_compressor` and take up the rest of the buffer
+pub fn write_run_streams<T, C: Compressor<Input = T>, K: Compressor<Input = u8>>(counts: &[u8], values: &[T], value_compressor: &C, count_compressor: &K, compressed: &mut Vec<u8>) {
+    let mut counts_section = Vec::<u8>::new();
+    count_compressor.compress(counts, &mut counts_section);
+    write_count_bytes(counts_section.len() as u64, compressed);
+    compressed.extend_from_slice(&counts_section);
+    value_compressor.compress(values, compressed);
+}
+
+// there are never more runs than elements, so `limit` runs and `counts_limit` bytes of encoded counts are enough
+pub fn read_run_streams<T, C: Compressor<Input = T>, K: Compressor<Input = u8>>(compressed: &[u8], value_compressor: &C, count_compressor: &K, limit: usize, counts_limit: usize) -> Result<(Vec<u8>, Vec<T>), CompressionError> {
+    let (section_length, bytes_read) = try_read_count_bytes(compressed)?;
+    let end = bytes_read.checked_add(section_length as usize).ok_or(CompressionError::Truncated)?;
+    let counts_section = compressed.get(bytes_read..end).ok_or(CompressionError::Truncated)?;
+
+    let mut counts = Vec::<u8>::new();
+    count_compressor.try_decompress_bounded(counts_section, &mut counts, counts_limit)?;
+
+    // the values run to the end of the buffer, so less than an element left over means it was cut off
+    let mut values = Vec::<T>::new();
+    match value_compressor.try_decompress_bounded(&compressed[end..], &mut values, limit) {
+        Err(CompressionError::TrailingBytes(bytes)) if bytes < size_of::<T>() => return Err(CompressionError::Truncated),
+        result => result?,
+    }
+
+    Ok((counts, values))
 }
 
 #[cfg(test)]
@@ -99,6 +182,44 @@ mod tests {
         assert_eq!(count, u8::MAX as u64);
     }
 
+    #[test]
+    fn test_count_bytes_are_little_endian() {
+        let mut buffer = Vec::new();
+        write_count_bytes(1000, &mut buffer);
+        write_count_bytes(100000, &mut buffer);
+        assert_eq!(buffer, [1, 0xe8, 0x03, 2, 0xa0, 0x86, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_read_count_truncated() {
+        let mut buffer = Vec::new();
+        write_count_bytes(100000, &mut buffer);
+        assert_eq!(try_read_count_bytes(&buffer[..3]), Err(CompressionError::Truncated));
+        assert_eq!(try_read_count_bytes(&[]), Err(CompressionError::Truncated));
+    }
+
+    #[test]
+    fn test_read_count_at_index() {
+        let mut buffer = Vec::new();
+        write_count_bytes(7, &mut buffer);
+        write_count_bytes(100000, &mut buffer);
+
+        let mut index = 0;
+        assert_eq!(try_read_count(&buffer, &mut index), Ok(7));
+        assert_eq!(try_read_count(&buffer, &mut index), Ok(100000));
+        assert_eq!(index, buffer.len());
+        assert_eq!(try_read_count(&buffer, &mut index), Err(CompressionError::Truncated));
+
+        // past the end is truncated too, not a panic
+        index = buffer.len() + 1;
+        assert_eq!(try_read_count(&buffer, &mut index), Err(CompressionError::Truncated));
+    }
+
+    #[test]
+    fn test_read_count_unknown_mode() {
+        assert_eq!(try_read_count_bytes(&[4, 0, 0]), Err(CompressionError::UnknownMode(4)));
+    }
+
     #[test]
     fn test_write_and_read_count_boundary_u16_max() {
         let mut buffer = Vec::new();
@@ -0,0 +1,275 @@
+use crate::error::CompressionError;
+use crate::algorithms::common::*;
+use crate::algorithms::bits::{BitReader, BitWriter};
+
+// a self delimiting encoding of a single count
+pub trait CountCodec {
+    const LAYOUT: CountLayout;
+
+    fn write_count(count: u64, buffer: &mut Vec<u8>);
+    // returns the count and the number of bytes it took up
+    fn try_read_count(buffer: &[u8]) -> Result<(u64, usize), CompressionError>;
+}
+
+// `write_count_bytes`: a mode byte, then 1, 2, 4 or 8 little endian bytes
+pub struct ModeByte;
+
+// 7 bits per byte, lowest first, the high bit is set on every byte but the last
+pub struct Leb128;
+
+// the trailing zeros of the first byte tell how many bytes (1 to 8) hold the count, 7 bits per byte,
+// a zero first byte is followed by the full 8 byte count
+// the length is known after the first byte, so decoding doesn't branch per byte like LEB128
+pub struct PrefixVarint;
+
+impl CountCodec for ModeByte {
+    const LAYOUT: CountLayout = CountLayout::ModeBytes;
+
+    fn write_count(count: u64, buffer: &mut Vec<u8>) {
+        write_count_bytes(count, buffer);
+    }
+
+    fn try_read_count(buffer: &[u8]) -> Result<(u64, usize), CompressionError> {
+        try_read_count_bytes(buffer)
+    }
+}
+
+impl CountCodec for Leb128 {
+    const LAYOUT: CountLayout = CountLayout::Leb128;
+
+    fn write_count(mut count: u64, buffer: &mut Vec<u8>) {
+        while count >= 0x80 {
+            buffer.push(count as u8 | 0x80);
+            count >>= 7;
+        }
+
+        buffer.push(count as u8);
+    }
+
+    fn try_read_count(buffer: &[u8]) -> Result<(u64, usize), CompressionError> {
+        let mut count = 0u64;
+
+        for (i, byte) in buffer.iter().enumerate() {
+            let bits = (*byte & 0x7f) as u64;
+            let shift = 7 * i as u32;
+
+            // the 10th byte only has room for the highest bit of a u64
+            if shift >= u64::BITS || (bits << shift) >> shift != bits {
+                return Err(CompressionError::InvalidCode);
+            }
+
+            count |= bits << shift;
+            if *byte & 0x80 == 0 {
+                return Ok((count, i + 1));
+            }
+        }
+
+        Err(CompressionError::Truncated)
+    }
+}
+
+impl CountCodec for PrefixVarint {
+    const LAYOUT: CountLayout = CountLayout::PrefixVarint;
+
+    fn write_count(count: u64, buffer: &mut Vec<u8>) {
+        let length = (u64::BITS - count.leading_zeros()).div_ceil(7).max(1);
+
+        if length > 8 {
+            buffer.push(0);
+            buffer.extend_from_slice(&u64::to_le_bytes(count));
+            return;
+        }
+
+        let encoded = (count << length) | (1 << (length - 1));
+        buffer.extend_from_slice(&u64::to_le_bytes(encoded)[..length as usize]);
+    }
+
+    fn try_read_count(buffer: &[u8]) -> Result<(u64, usize), CompressionError> {
+        let first = *buffer.first().ok_or(CompressionError::Truncated)?;
+
+        if first == 0 {
+            let bytes = buffer.get(1..9).ok_or(CompressionError::Truncated)?;
+            return Ok((u64::from_le_bytes(bytes.try_into().unwrap()), 9));
+        }
+
+        let length = first.trailing_zeros() as usize + 1;
+
+        // a single unaligned load whenever there is a whole word left
+        if let Some(bytes) = buffer.get(..8) {
+            let word = u64::from_le_bytes(bytes.try_into().unwrap());
+            let mask = u64::MAX >> (64 - 8 * length);
+            return Ok(((word & mask) >> length, length));
+        }
+
+        let mut bytes = [0u8; 8];
+        bytes[..length].copy_from_slice(buffer.get(..length).ok_or(CompressionError::Truncated)?);
+        Ok((u64::from_le_bytes(bytes) >> length, length))
+    }
+}
+
+// how a list of counts is laid out, stored as a header byte in front of the counts
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountLayout {
+    // `ModeByte` for every count
+    ModeBytes = 0,
+    // the number of counts, then an Elias gamma bitstream of every count + 1 (so zeros can be stored)
+    Gamma = 1,
+    // `Leb128` for every count
+    Leb128 = 2,
+    // `PrefixVarint` for every count
+    PrefixVarint = 3,
+}
+
+fn write_all<C: CountCodec>(counts: &[u64], buffer: &mut Vec<u8>) {
+    for count in counts {
+        C::write_count(*count, buffer);
+    }
+}
+
+fn read_all<C: CountCodec>(buffer: &[u8], counts: &mut Vec<u64>) -> Result<(), CompressionError> {
+    let mut index = 0;
+    while index < buffer.len() {
+        let (count, bytes_read) = C::try_read_count(&buffer[index..])?;
+        counts.push(count);
+        index += bytes_read;
+    }
+
+    Ok(())
+}
+
+impl CountLayout {
+    pub const ALL: [CountLayout; 4] = [CountLayout::ModeBytes, CountLayout::Gamma, CountLayout::Leb128, CountLayout::PrefixVarint];
+
+    pub fn from_u8(layout: u8) -> Result<Self, CompressionError> {
+        Self::ALL.get(layout as usize).copied().ok_or(CompressionError::InvalidHeader("unknown count layout"))
+    }
+
+    // the most bytes `counts` counts can take up, header included, gamma codes of 64 bit counts are the longest
+    pub fn max_length(self, counts: usize) -> usize {
+        let per_count = match self {
+            Self::ModeBytes | Self::PrefixVarint => 9,
+            Self::Leb128 => 10,
+            Self::Gamma => 17,
+        };
+
+        counts.saturating_mul(per_count).saturating_add(9)
+    }
+
+    // does not write the layout byte itself
+    pub fn write_counts(self, counts: &[u64], buffer: &mut Vec<u8>) {
+        match self {
+            Self::ModeBytes => write_all::<ModeByte>(counts, buffer),
+            Self::Leb128 => write_all::<Leb128>(counts, buffer),
+            Self::PrefixVarint => write_all::<PrefixVarint>(counts, buffer),
+            Self::Gamma => {
+                write_count_bytes(counts.len() as u64, buffer);
+                let mut writer = BitWriter::new(buffer);
+                for count in counts {
+                    writer.write_gamma(count + 1);
+                }
+
+                writer.finish();
+            },
+        }
+    }
+
+    // reads counts until the end of `buffer`
+    pub fn try_read_counts(self, buffer: &[u8]) -> Result<Vec<u64>, CompressionError> {
+        let mut counts = Vec::<u64>::new();
+
+        match self {
+            Self::ModeBytes => read_all::<ModeByte>(buffer, &mut counts)?,
+            Self::Leb128 => read_all::<Leb128>(buffer, &mut counts)?,
+            Self::PrefixVarint => read_all::<PrefixVarint>(buffer, &mut counts)?,
+            Self::Gamma => {
+                let (length, bytes_read) = try_read_count_bytes(buffer)?;
+                let mut reader = BitReader::new(&buffer[bytes_read..]);
+                for _ in 0..length {
+                    counts.push(reader.read_gamma()? - 1);
+                }
+
+                if bytes_read + reader.bytes_consumed() != buffer.len() {
+                    return Err(CompressionError::TrailingBytes(buffer.len() - bytes_read - reader.bytes_consumed()));
+                }
+            },
+        }
+
+        Ok(counts)
+    }
+
+    // the layout that takes up the fewest bytes for `counts`, ties go to the lower layout byte
+    pub fn smallest(counts: &[u64]) -> Self {
+        // gamma stores count + 1, which doesn't fit for the largest count
+        let gamma = counts.iter().all(|count| *count < u64::MAX);
+
+        let mut buffer = Vec::new();
+        Self::ALL.into_iter().filter(|layout| gamma || *layout != Self::Gamma).min_by_key(|layout| {
+            buffer.clear();
+            layout.write_counts(counts, &mut buffer);
+            buffer.len()
+        }).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<C: CountCodec>(count: u64) -> usize {
+        let mut buffer = Vec::new();
+        C::write_count(count, &mut buffer);
+        assert_eq!(C::try_read_count(&buffer), Ok((count, buffer.len())));
+        buffer.len()
+    }
+
+    #[test]
+    fn test_codec_sizes() {
+        assert_eq!(round_trip::<ModeByte>(3), 2);
+        assert_eq!(round_trip::<Leb128>(3), 1);
+        assert_eq!(round_trip::<PrefixVarint>(3), 1);
+
+        assert_eq!(round_trip::<ModeByte>(300), 3);
+        assert_eq!(round_trip::<Leb128>(300), 2);
+        assert_eq!(round_trip::<PrefixVarint>(300), 2);
+
+        assert_eq!(round_trip::<Leb128>(u64::MAX), 10);
+        assert_eq!(round_trip::<PrefixVarint>(u64::MAX), 9);
+        assert_eq!(round_trip::<PrefixVarint>((1 << 56) - 1), 8);
+        assert_eq!(round_trip::<PrefixVarint>(1 << 56), 9);
+    }
+
+    #[test]
+    fn test_codec_boundaries() {
+        for shift in 0..64 {
+            for count in [(1u64 << shift) - 1, 1 << shift, (1 << shift) + 1] {
+                round_trip::<ModeByte>(count);
+                round_trip::<Leb128>(count);
+                round_trip::<PrefixVarint>(count);
+            }
+        }
+    }
+
+    #[test]
+    fn test_codec_invalid_input() {
+        assert_eq!(Leb128::try_read_count(&[0x80, 0x80]), Err(CompressionError::Truncated));
+        assert_eq!(Leb128::try_read_count(&[0xff; 11]), Err(CompressionError::InvalidCode));
+        assert_eq!(PrefixVarint::try_read_count(&[0b0000_0100, 1]), Err(CompressionError::Truncated));
+        assert_eq!(PrefixVarint::try_read_count(&[0, 1, 2]), Err(CompressionError::Truncated));
+    }
+
+    #[test]
+    fn test_layouts_round_trip() {
+        let counts = [0u64, 1, 2, 3, 127, 128, 300, 70_000, 1 << 40];
+        for layout in CountLayout::ALL {
+            let mut buffer = Vec::new();
+            layout.write_counts(&counts, &mut buffer);
+            assert_eq!(layout.try_read_counts(&buffer).unwrap(), counts);
+            assert_eq!(CountLayout::from_u8(layout as u8), Ok(layout));
+        }
+
+        assert_eq!(CountLayout::smallest(&[1, 2, 1, 3, 1, 1]), CountLayout::Gamma);
+        assert_eq!(CountLayout::smallest(&[300, 300, 5000]), CountLayout::Leb128);
+        assert_eq!(CountLayout::smallest(&[u64::MAX]), CountLayout::ModeBytes);
+    }
+}
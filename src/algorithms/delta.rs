@@ -1,44 +1,73 @@
-use std::{io::{BufReader, Read, Write}, marker::PhantomData, ops::{Add, Sub}};
-use rayon::{iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator}, slice::ParallelSlice, *};
-use bytemuck::{Pod, Zeroable};
+use std::marker::PhantomData;
 use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::integer::Integer;
 
-pub struct Delta<T: Pod + PartialEq + Sub<T, Output = T> + Add<T, Output = T>> {
+// stores the zigzagged difference between consecutive elements and hands those residuals to the inner compressor
+pub struct Delta<T: Integer, C: Compressor<Input = T::Unsigned> = NaiveCompressor<<T as Integer>::Unsigned>> {
+    compressor: C,
     _phantom: PhantomData<T>,
 }
 
-impl<T: Pod + PartialEq + Sub<T, Output = T> + Add<T, Output = T>> Compressor for Delta<T> {
-    type Input = T;
-    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) { 
-        let mut previous = uncompressed[0];
-        compressed.extend_from_slice(bytemuck::bytes_of(&[previous]));
-    
-        for x in uncompressed[1..].iter() {
-            let delta = *x - previous;
-            previous = *x;
-            let slice = bytemuck::bytes_of(&delta);
-            compressed.extend_from_slice(&slice);
+impl<T: Integer, C: Compressor<Input = T::Unsigned>> Delta<T, C> {
+    pub fn new_with(compressor: C) -> Self {
+        Self {
+            compressor,
+            _phantom: Default::default()
         }
     }
+}
 
-    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<T>) {
-        let first = bytemuck::from_bytes::<T>(&compressed[0..(size_of::<T>())]);
-        let mut index = size_of::<T>();
+// the first residual is the first element itself (delta against zero)
+pub fn encode_residuals<T: Integer>(uncompressed: &[T], residuals: &mut Vec<T::Unsigned>) {
+    let mut previous = T::zeroed();
 
-        while index <= compressed.len() {
-            let delta = bytemuck::from_bytes::<T>(&compressed[0..(size_of::<T>())]);
-            let new = *first + *delta;
+    residuals.extend(uncompressed.iter().map(|x| {
+        let delta = x.wrapping_sub(previous);
+        previous = *x;
+        delta.zigzag()
+    }));
+}
 
-            
+pub fn decode_residuals<T: Integer>(residuals: &[T::Unsigned], uncompressed: &mut Vec<T>) {
+    let mut previous = T::zeroed();
 
-            index += size_of::<T>();
-        }
+    uncompressed.extend(residuals.iter().map(|residual| {
+        previous = previous.wrapping_add(T::unzigzag(*residual));
+        previous
+    }));
+}
+
+impl<T: Integer, C: Compressor<Input = T::Unsigned>> Compressor for Delta<T, C> {
+    type Input = T;
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        let mut residuals = Vec::<T::Unsigned>::with_capacity(uncompressed.len());
+        encode_residuals(uncompressed, &mut residuals);
+        self.compressor.compress(&residuals, compressed);
+    }
+
+    fn compress_bounded(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        let mut residuals = Vec::<T::Unsigned>::with_capacity(uncompressed.len());
+        encode_residuals(uncompressed, &mut residuals);
+        self.compressor.compress_bounded(&residuals, compressed, limit)
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut residuals = Vec::<T::Unsigned>::new();
+        self.compressor.try_decompress_bounded(compressed, &mut residuals, limit)?;
+        decode_residuals(&residuals, uncompressed);
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::Delta, vec![self.compressor.descriptor()])
     }
 
     fn new() -> Self {
         Self {
+            compressor: C::new(),
             _phantom: Default::default()
         }
     }
 }
-
@@ -0,0 +1,157 @@
+use std::marker::PhantomData;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
+use crate::algorithms::integer::Integer;
+use crate::algorithms::bits::{BitReader, BitWriter};
+
+// value widths of the short buckets, anything larger is stored with the full width of the element
+const BUCKET_BITS: [u32; 3] = [7, 9, 12];
+
+// stores the zigzagged difference between consecutive deltas and hands those to the inner compressor
+// a series with a fixed interval turns into the first element, the interval and then only zeros
+pub struct DeltaOfDelta<T: Integer, C: Compressor<Input = T::Unsigned> = Buckets<<T as Integer>::Unsigned>> {
+    compressor: C,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Integer, C: Compressor<Input = T::Unsigned>> DeltaOfDelta<T, C> {
+    pub fn new_with(compressor: C) -> Self {
+        Self {
+            compressor,
+            _phantom: Default::default()
+        }
+    }
+}
+
+// variable width encoding for small unsigned values (like zigzagged residuals), as in Gorilla's timestamps:
+//   0                      zero
+//   10, 110, 1110 + bits   fits in 7, 9 or 12 bits
+//   1111 + bits            full width
+// layout: element count, then the bitstream
+pub struct Buckets<T: Integer> {
+    _phantom: PhantomData<T>,
+}
+
+// the first residual is the first element, the second one its delta to the first element
+pub fn encode_second_residuals<T: Integer>(uncompressed: &[T], residuals: &mut Vec<T::Unsigned>) {
+    let mut previous = T::zeroed();
+    let mut previous_delta = T::zeroed();
+
+    residuals.extend(uncompressed.iter().enumerate().map(|(i, x)| {
+        let delta = x.wrapping_sub(previous);
+        let residual = delta.wrapping_sub(previous_delta).zigzag();
+        previous = *x;
+        previous_delta = if i == 0 { T::zeroed() } else { delta };
+        residual
+    }));
+}
+
+pub fn decode_second_residuals<T: Integer>(residuals: &[T::Unsigned], uncompressed: &mut Vec<T>) {
+    let mut previous = T::zeroed();
+    let mut previous_delta = T::zeroed();
+
+    uncompressed.extend(residuals.iter().enumerate().map(|(i, residual)| {
+        let delta = previous_delta.wrapping_add(T::unzigzag(*residual));
+        previous = previous.wrapping_add(delta);
+        previous_delta = if i == 0 { T::zeroed() } else { delta };
+        previous
+    }));
+}
+
+impl<T: Integer, C: Compressor<Input = T::Unsigned>> Compressor for DeltaOfDelta<T, C> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        let mut residuals = Vec::<T::Unsigned>::with_capacity(uncompressed.len());
+        encode_second_residuals(uncompressed, &mut residuals);
+        self.compressor.compress(&residuals, compressed);
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut residuals = Vec::<T::Unsigned>::new();
+        self.compressor.try_decompress_bounded(compressed, &mut residuals, limit)?;
+        decode_second_residuals(&residuals, uncompressed);
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::DeltaOfDelta, vec![self.compressor.descriptor()])
+    }
+
+    fn new() -> Self {
+        Self::new_with(C::new())
+    }
+}
+
+impl<T: Integer> Compressor for Buckets<T> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        write_count_bytes(uncompressed.len() as u64, compressed);
+        let mut writer = BitWriter::new(compressed);
+
+        for value in uncompressed {
+            // only the order matters here, so this is the value itself for unsigned elements
+            let value = value.to_ordered();
+
+            if value == 0 {
+                writer.write_bit(false);
+                continue;
+            }
+
+            match BUCKET_BITS.iter().position(|bits| value >> bits == 0) {
+                Some(bucket) => {
+                    writer.write_unary(bucket as u64 + 1);
+                    writer.write_bits(value, BUCKET_BITS[bucket]);
+                },
+                None => {
+                    writer.write_bits(0b1111, 4);
+                    writer.write_bits(value, T::BITS);
+                },
+            }
+        }
+
+        writer.finish();
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let (count, bytes_read) = try_read_count_bytes(compressed)?;
+        check_limit(count, limit)?;
+        let mut reader = BitReader::new(&compressed[bytes_read..]);
+
+        for _ in 0..count {
+            let mut ones = 0;
+            while ones < 4 && reader.read_bit()? {
+                ones += 1;
+            }
+
+            let value = match ones {
+                0 => 0,
+                4 => reader.read_bits(T::BITS)?,
+                bucket => reader.read_bits(BUCKET_BITS[bucket - 1])?,
+            };
+
+            uncompressed.push(T::from_ordered(value));
+        }
+
+        // only the padding in the last byte may be left over
+        let end = bytes_read + reader.bytes_consumed();
+        if end != compressed.len() {
+            return Err(CompressionError::TrailingBytes(compressed.len() - end));
+        }
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::Buckets)
+    }
+
+    fn new() -> Self {
+        Self {
+            _phantom: Default::default(),
+        }
+    }
+}
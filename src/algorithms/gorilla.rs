@@ -0,0 +1,170 @@
+use std::marker::PhantomData;
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
+use crate::algorithms::bits::{BitReader, BitWriter};
+
+// Gorilla (Facebook's time series float compression) over the raw bits of 32 or 64 bit elements
+// the first element is stored as is, every next one is XORed with its predecessor:
+//   0                                   same value
+//   10 + meaningful bits                the XOR fits in the previous leading/trailing zero window
+//   11 + leading zeros + length + bits  a new window
+// layout: element count, then the bitstream
+pub struct Gorilla<T: GorillaElement> {
+    _phantom: PhantomData<T>,
+}
+
+// 32 and 64 bit elements, meant for f32 and f64, but Gorilla works on the bits of integers just as well
+// the element is widened to a u64 with its bits unchanged
+pub trait GorillaElement: Pod {
+    const BITS: u32;
+
+    fn to_raw_bits(self) -> u64;
+    fn from_raw_bits(bits: u64) -> Self;
+}
+
+macro_rules! impl_gorilla_element {
+    ($($t:ty => $bits:ty),* $(,)?) => {
+        $(
+            impl GorillaElement for $t {
+                const BITS: u32 = <$bits>::BITS;
+
+                fn to_raw_bits(self) -> u64 {
+                    bytemuck::cast::<$t, $bits>(self) as u64
+                }
+
+                fn from_raw_bits(bits: u64) -> Self {
+                    bytemuck::cast::<$bits, $t>(bits as $bits)
+                }
+            }
+        )*
+    };
+}
+
+impl_gorilla_element! {
+    f32 => u32,
+    f64 => u64,
+    u32 => u32,
+    u64 => u64,
+    i32 => u32,
+    i64 => u64,
+}
+
+impl<T: GorillaElement> Gorilla<T> {
+    const BITS: u32 = T::BITS;
+
+    // 5 bits of leading zeros and 6 bits of length for 64 bit elements, one bit less of each for 32 bit ones
+    const LEADING_BITS: u32 = Self::BITS.trailing_zeros() - 1;
+    const LENGTH_BITS: u32 = Self::BITS.trailing_zeros();
+}
+
+impl<T: GorillaElement> Compressor for Gorilla<T> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        write_count_bytes(uncompressed.len() as u64, compressed);
+
+        let Some((first, rest)) = uncompressed.split_first() else {
+            return;
+        };
+
+        let mut writer = BitWriter::new(compressed);
+        let mut previous = first.to_raw_bits();
+        writer.write_bits(previous, Self::BITS);
+
+        // no window yet, so the first non zero XOR always opens one
+        let mut window: Option<(u32, u32)> = None;
+        let max_leading = (1 << Self::LEADING_BITS) - 1;
+
+        for value in rest {
+            let bits = value.to_raw_bits();
+            let xor = bits ^ previous;
+            previous = bits;
+
+            if xor == 0 {
+                writer.write_bit(false);
+                continue;
+            }
+
+            // leading zeros count from the top of the element, not of the u64
+            let leading = (xor.leading_zeros() - (u64::BITS - Self::BITS)).min(max_leading);
+            let trailing = xor.trailing_zeros();
+
+            match window {
+                Some((window_leading, window_trailing)) if leading >= window_leading && trailing >= window_trailing => {
+                    writer.write_bits(0b10, 2);
+                    writer.write_bits(xor >> window_trailing, Self::BITS - window_leading - window_trailing);
+                },
+                _ => {
+                    let length = Self::BITS - leading - trailing;
+                    writer.write_bits(0b11, 2);
+                    writer.write_bits(leading as u64, Self::LEADING_BITS);
+                    writer.write_bits(length as u64 - 1, Self::LENGTH_BITS);
+                    writer.write_bits(xor >> trailing, length);
+                    window = Some((leading, trailing));
+                },
+            }
+        }
+
+        writer.finish();
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let (count, bytes_read) = try_read_count_bytes(compressed)?;
+        check_limit(count, limit)?;
+        if count == 0 {
+            if bytes_read != compressed.len() {
+                return Err(CompressionError::TrailingBytes(compressed.len() - bytes_read));
+            }
+
+            return Ok(());
+        }
+
+        let mut reader = BitReader::new(&compressed[bytes_read..]);
+        let mut previous = reader.read_bits(Self::BITS)?;
+        uncompressed.push(T::from_raw_bits(previous));
+
+        let mut window: Option<(u32, u32)> = None;
+
+        for _ in 1..count {
+            if reader.read_bit()? {
+                let (leading, trailing) = if reader.read_bit()? {
+                    let leading = reader.read_bits(Self::LEADING_BITS)? as u32;
+                    let length = reader.read_bits(Self::LENGTH_BITS)? as u32 + 1;
+                    if leading + length > Self::BITS {
+                        return Err(CompressionError::InvalidCode);
+                    }
+
+                    (leading, Self::BITS - leading - length)
+                } else {
+                    window.ok_or(CompressionError::InvalidCode)?
+                };
+
+                window = Some((leading, trailing));
+                previous ^= reader.read_bits(Self::BITS - leading - trailing)? << trailing;
+            }
+
+            uncompressed.push(T::from_raw_bits(previous));
+        }
+
+        // only the padding in the last byte may be left over
+        let end = bytes_read + reader.bytes_consumed();
+        if end != compressed.len() {
+            return Err(CompressionError::TrailingBytes(compressed.len() - end));
+        }
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::Gorilla)
+    }
+
+    fn new() -> Self {
+        Self {
+            _phantom: Default::default(),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::{cmp::Reverse, collections::BinaryHeap, marker::PhantomData};
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
+use crate::algorithms::bits::{BitReader, BitWriter};
+
+// code lengths have to fit in the 4 bit entries of the code length table
+const MAX_CODE_LENGTH: u8 = 15;
+
+// canonical Huffman over the raw bytes of the elements
+// layout: byte count, then a single bitstream holding the code length table followed by the codes
+// the table stores a 4 bit length per byte value, a zero length is followed by 8 bits of extra zeros
+pub struct Huffman<T: Pod = u8> {
+    _phantom: PhantomData<T>,
+}
+
+fn huffman_code_lengths(frequencies: &[u64; 256]) -> [u8; 256] {
+    let mut lengths = [0u8; 256];
+    let used = (0..256).filter(|symbol| frequencies[*symbol] > 0).collect::<Vec<_>>();
+
+    // a lone symbol still needs a single bit per occurence
+    if used.len() == 1 {
+        lengths[used[0]] = 1;
+    }
+
+    if used.len() < 2 {
+        return lengths;
+    }
+
+    // leaves are the first nodes, every merge adds a new parent node
+    let mut parents = vec![usize::MAX; used.len()];
+    let mut heap = used.iter().enumerate()
+        .map(|(node, symbol)| Reverse((frequencies[*symbol], node)))
+        .collect::<BinaryHeap<_>>();
+
+    while heap.len() > 1 {
+        let Reverse((first_weight, first)) = heap.pop().unwrap();
+        let Reverse((second_weight, second)) = heap.pop().unwrap();
+        let parent = parents.len();
+        parents.push(usize::MAX);
+        parents[first] = parent;
+        parents[second] = parent;
+        heap.push(Reverse((first_weight + second_weight, parent)));
+    }
+
+    for (leaf, symbol) in used.iter().enumerate() {
+        let mut node = leaf;
+        let mut depth = 0u8;
+
+        while parents[node] != usize::MAX {
+            node = parents[node];
+            depth = depth.saturating_add(1);
+        }
+
+        lengths[*symbol] = depth;
+    }
+
+    lengths
+}
+
+// flattens the frequencies until no code is longer than `MAX_CODE_LENGTH`
+fn limited_code_lengths(frequencies: &[u64; 256]) -> [u8; 256] {
+    let mut frequencies = *frequencies;
+
+    loop {
+        let lengths = huffman_code_lengths(&frequencies);
+        if lengths.iter().all(|length| *length <= MAX_CODE_LENGTH) {
+            return lengths;
+        }
+
+        for frequency in frequencies.iter_mut().filter(|frequency| **frequency > 0) {
+            *frequency = (*frequency >> 1).max(1);
+        }
+    }
+}
+
+// symbols sorted by (length, symbol) get consecutive codes
+fn canonical_codes(lengths: &[u8; 256]) -> [u32; 256] {
+    let mut symbols = (0..256).filter(|symbol| lengths[*symbol] > 0).collect::<Vec<_>>();
+    symbols.sort_by_key(|symbol| (lengths[*symbol], *symbol));
+
+    let mut codes = [0u32; 256];
+    let mut code = 0u32;
+    let mut previous_length = 0u8;
+
+    for symbol in symbols {
+        code <<= lengths[symbol] - previous_length;
+        codes[symbol] = code;
+        code += 1;
+        previous_length = lengths[symbol];
+    }
+
+    codes
+}
+
+fn write_code_lengths(lengths: &[u8; 256], writer: &mut BitWriter) {
+    let mut symbol = 0;
+
+    while symbol < 256 {
+        writer.write_bits(lengths[symbol] as u64, 4);
+
+        if lengths[symbol] == 0 {
+            let zeros = lengths[(symbol + 1)..].iter().take(255).take_while(|length| **length == 0).count();
+            writer.write_bits(zeros as u64, 8);
+            symbol += zeros;
+        }
+
+        symbol += 1;
+    }
+}
+
+fn read_code_lengths(reader: &mut BitReader) -> Result<[u8; 256], CompressionError> {
+    let mut lengths = [0u8; 256];
+    let mut symbol = 0;
+
+    while symbol < 256 {
+        lengths[symbol] = reader.read_bits(4)? as u8;
+
+        if lengths[symbol] == 0 {
+            symbol += reader.read_bits(8)? as usize;
+        }
+
+        symbol += 1;
+    }
+
+    if symbol > 256 {
+        return Err(CompressionError::InvalidCode);
+    }
+
+    // an over-subscribed table (kraft sum above 1) can't come from a valid prefix code
+    let kraft = lengths.iter().filter(|length| **length > 0).map(|length| 1u32 << (MAX_CODE_LENGTH - length)).sum::<u32>();
+    if kraft > 1 << MAX_CODE_LENGTH {
+        return Err(CompressionError::InvalidCode);
+    }
+
+    Ok(lengths)
+}
+
+// canonical decoding table: for every length, the first code and where its symbols start
+struct Decoder {
+    first_code: [u32; MAX_CODE_LENGTH as usize + 1],
+    first_index: [usize; MAX_CODE_LENGTH as usize + 1],
+    counts: [u32; MAX_CODE_LENGTH as usize + 1],
+    symbols: Vec<u8>,
+}
+
+impl Decoder {
+    fn new(lengths: &[u8; 256]) -> Self {
+        let mut symbols = (0..256).filter(|symbol| lengths[*symbol] > 0).collect::<Vec<_>>();
+        symbols.sort_by_key(|symbol| (lengths[*symbol], *symbol));
+
+        let mut counts = [0u32; MAX_CODE_LENGTH as usize + 1];
+        for symbol in symbols.iter() {
+            counts[lengths[*symbol] as usize] += 1;
+        }
+
+        let mut first_code = [0u32; MAX_CODE_LENGTH as usize + 1];
+        let mut first_index = [0usize; MAX_CODE_LENGTH as usize + 1];
+        let mut code = 0u32;
+        let mut index = 0usize;
+
+        for length in 1..=(MAX_CODE_LENGTH as usize) {
+            first_code[length] = code;
+            first_index[length] = index;
+            code = (code + counts[length]) << 1;
+            index += counts[length] as usize;
+        }
+
+        Self { first_code, first_index, counts, symbols: symbols.into_iter().map(|symbol| symbol as u8).collect() }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u8, CompressionError> {
+        let mut code = 0u32;
+
+        for length in 1..=(MAX_CODE_LENGTH as usize) {
+            code = (code << 1) | reader.read_bit()? as u32;
+            let offset = code.wrapping_sub(self.first_code[length]);
+
+            if offset < self.counts[length] {
+                return Ok(self.symbols[self.first_index[length] + offset as usize]);
+            }
+        }
+
+        Err(CompressionError::InvalidCode)
+    }
+}
+
+impl<T: Pod> Compressor for Huffman<T> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        let bytes = bytemuck::cast_slice::<T, u8>(uncompressed);
+        write_count_bytes(bytes.len() as u64, compressed);
+
+        if bytes.is_empty() {
+            return;
+        }
+
+        let mut frequencies = [0u64; 256];
+        for byte in bytes {
+            frequencies[*byte as usize] += 1;
+        }
+
+        let lengths = limited_code_lengths(&frequencies);
+        let codes = canonical_codes(&lengths);
+
+        let mut writer = BitWriter::new(compressed);
+        write_code_lengths(&lengths, &mut writer);
+
+        for byte in bytes {
+            writer.write_bits(codes[*byte as usize] as u64, lengths[*byte as usize] as u32);
+        }
+
+        writer.finish();
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let (count, bytes_read) = try_read_count_bytes(compressed)?;
+        if count % size_of::<T>() as u64 != 0 {
+            return Err(CompressionError::Truncated);
+        }
+
+        check_limit(count / size_of::<T>() as u64, limit)?;
+
+        if count == 0 {
+            if bytes_read != compressed.len() {
+                return Err(CompressionError::TrailingBytes(compressed.len() - bytes_read));
+            }
+
+            return Ok(());
+        }
+
+        let mut reader = BitReader::new(&compressed[bytes_read..]);
+        let decoder = Decoder::new(&read_code_lengths(&mut reader)?);
+
+        // every symbol takes at least one bit, so this can't be larger than the input
+        let mut bytes = Vec::<u8>::with_capacity(count.min(compressed.len() as u64 * 8) as usize);
+        for _ in 0..count {
+            bytes.push(decoder.decode(&mut reader)?);
+        }
+
+        // only the padding in the last byte may be left over
+        let end = bytes_read + reader.bytes_consumed();
+        if end != compressed.len() {
+            return Err(CompressionError::TrailingBytes(compressed.len() - end));
+        }
+
+        uncompressed.extend(bytes.chunks_exact(size_of::<T>()).map(bytemuck::pod_read_unaligned::<T>));
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::Huffman)
+    }
+
+    fn new() -> Self {
+        Self {
+            _phantom: Default::default(),
+        }
+    }
+}
@@ -1,63 +1,301 @@
-use std::{io::{BufReader, Read, Write}, marker::PhantomData, ops::Sub};
-use rayon::{iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator}, slice::ParallelSlice, *};
-use bytemuck::{Pod, Zeroable};
+use std::marker::PhantomData;
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+use std::time::{Duration, Instant};
+use bytemuck::Pod;
+use rayon::{ThreadPool, iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator}};
 use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor, DynCompressor};
 
-pub struct Hybrid<T: Pod + PartialEq> {
+// how Hybrid picks the algorithm it compresses the whole input with
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Selection {
+    // every algorithm compresses all of the input
+    Exhaustive,
+    // every algorithm compresses `samples` evenly spaced runs of `sample_length` elements and only the best one all of the input
+    // falls back to exhaustive when the input isn't at least twice as long as the samples or the confidence is below `min_confidence`
+    Sampled { samples: usize, sample_length: usize, min_confidence: f64 },
+}
+
+impl Selection {
+    pub fn sampled() -> Self {
+        Self::Sampled { samples: 8, sample_length: 1024, min_confidence: 0.5 }
+    }
+}
+
+// what trying one algorithm on the input cost
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trial {
+    pub size: usize,
+    pub encode: Duration,
+    // zero unless the objective needs it
+    pub decode: Duration,
+}
+
+// what Hybrid minimizes when picking an algorithm, ties go to the algorithm added first
+#[derive(Clone)]
+pub enum Objective {
+    // the smallest output
+    Ratio,
+    // the output size plus `bytes_per_millisecond` bytes for every millisecond it takes to decode
+    DecodeWeighted { bytes_per_millisecond: f64 },
+    // any cost of a trial
+    Custom(Arc<dyn Fn(&Trial) -> f64 + Send + Sync>),
+}
+
+impl Objective {
+    pub fn custom(cost: impl Fn(&Trial) -> f64 + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(cost))
+    }
+
+    pub fn cost(&self, trial: &Trial) -> f64 {
+        match self {
+            Self::Ratio => trial.size as f64,
+            Self::DecodeWeighted { bytes_per_millisecond } => trial.size as f64 + bytes_per_millisecond * trial.decode.as_secs_f64() * 1000.0,
+            Self::Custom(cost) => cost(trial),
+        }
+    }
+
+    // whether candidates have to be decoded to be compared
+    fn needs_decode(&self) -> bool {
+        !matches!(self, Self::Ratio)
+    }
+}
+
+// which algorithm a Hybrid picked and how sure it was about it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SelectionReport {
+    pub index: usize,
+    // compressed over uncompressed size, of the samples when the algorithm was picked from samples
+    pub estimated_ratio: f64,
+    // share of the samples on which the picked algorithm was (one of) the smallest, 1 when nothing was sampled
+    pub confidence: f64,
+    // whether every algorithm ended up compressing all of the input
+    pub exhaustive: bool,
+    // cost of the picked algorithm, on the samples when it was picked from samples
+    pub cost: f64,
+}
+
+// tries every algorithm and keeps the smallest output, ties go to the algorithm added first
+// layout: index of the algorithm, then its output
+pub struct Hybrid<T: Pod + PartialEq + Send + Sync> {
     _phantom: PhantomData<T>,
-    algorithms: Vec<Box<dyn Compressor<Input = T> + Sync + Send>>,
+    algorithms: Vec<DynCompressor<T>>,
+    selection: Selection,
+    objective: Objective,
+
+    // tries the algorithms on `pool` (rayon's global pool if there is none) instead of one after another
+    parallel: bool,
+    pool: Option<Arc<ThreadPool>>,
+
+    // stops an algorithm as soon as its output is larger than the best one so far, only with `Objective::Ratio`
+    size_bound: bool,
 }
 
-impl<T: Pod + PartialEq> Hybrid<T> {
-    pub fn add<C: Compressor<Input = T> + 'static + Send + Sync>(mut self) -> Self {
-        let boxed = Box::new(C::new());
-        let boxed: Box<dyn Compressor<Input = T> + Sync + Send> = boxed; 
-        self.algorithms.push(boxed);
-        self
+// index, output and cost of an algorithm
+type Candidate = Option<(usize, Vec<u8>, f64)>;
+
+// the cheaper of two candidates, so that only the best one is kept around
+fn better(a: Candidate, b: Candidate) -> Candidate {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.2.total_cmp(&a.2).then(b.0.cmp(&a.0)).is_lt() { b } else { a }),
+        (a, None) => a,
+        (None, b) => b,
     }
 }
 
-impl<T: Pod + PartialEq> Compressor for Hybrid<T> {
-    type Input = T;
+impl<T: Pod + PartialEq + Send + Sync> Hybrid<T> {
+    pub fn add<C: Compressor<Input = T> + 'static + Send + Sync>(self) -> Self {
+        self.with(Box::new(C::new()))
+    }
+
+    pub fn with(mut self, compressor: DynCompressor<T>) -> Self {
+        self.algorithms.push(compressor);
+        self
+    }
+
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    // also turns on parallel evaluation
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self.parallel = true;
+        self
+    }
+
+    // only some algorithms actually stop early: LZ, BitPack, PFor and Naive, and Lookup when its input has more
+    // distinct elements than fit, along with Delta and Hybrid around them
+    // the others compress everything first and are only rejected afterwards
+    pub fn with_size_bound(mut self, size_bound: bool) -> Self {
+        self.size_bound = size_bound;
+        self
+    }
+
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        self.selection = selection;
+        self
+    }
 
-    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) { 
-        assert!(self.algorithms.len() > 0);
+    pub fn with_objective(mut self, objective: Objective) -> Self {
+        self.objective = objective;
+        self
+    }
+
+    // `compress` that also tells which algorithm it picked
+    pub fn compress_with_report(&self, uncompressed: &[T], compressed: &mut Vec<u8>) -> SelectionReport {
+        self.compress_selected(uncompressed, compressed, usize::MAX).expect("an unbounded algorithm always finishes")
+    }
+
+    // runs `job` on the pool, if there is one
+    fn install<R: Send>(&self, job: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(job),
+            None => job(),
+        }
+    }
+
+    // compresses with `algo` and times what the objective needs, None if the output doesn't fit in `limit` bytes
+    fn trial(&self, algo: &DynCompressor<T>, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> Option<Trial> {
+        let start = Instant::now();
+        if !algo.compress_bounded(uncompressed, compressed, limit) {
+            return None;
+        }
+        let encode = start.elapsed();
+
+        let mut decode = Duration::ZERO;
+        if self.objective.needs_decode() {
+            let mut decompressed = Vec::<T>::with_capacity(uncompressed.len());
+            let start = Instant::now();
+            algo.decompress(compressed, &mut decompressed);
+            decode = start.elapsed();
+        }
+
+        Some(Trial { size: compressed.len(), encode, decode })
+    }
+
+    // the index, output and cost of the cheapest algorithm that fits in `limit` bytes
+    fn best_candidate(&self, uncompressed: &[T], limit: usize) -> Candidate {
+        let size_bound = self.size_bound && matches!(self.objective, Objective::Ratio);
+        let best_size = AtomicUsize::new(limit);
+        let candidate = |(i, algo): (usize, &DynCompressor<T>)| -> Candidate {
+            let bound = if size_bound { best_size.load(Ordering::Relaxed) } else { limit };
+            let mut test_compressed = Vec::<u8>::new();
+            let trial = self.trial(algo, uncompressed, &mut test_compressed, bound)?;
+
+            best_size.fetch_min(test_compressed.len(), Ordering::Relaxed);
+            Some((i, test_compressed, self.objective.cost(&trial)))
+        };
+
+        if !self.parallel {
+            return self.algorithms.iter().enumerate().map(candidate).fold(None, better);
+        }
+
+        self.install(|| self.algorithms.par_iter().enumerate().map(candidate).reduce(|| None, better))
+    }
+
+    // the index of the algorithm with the lowest total cost on the samples, its ratio and cost on them and the confidence
+    fn sample(&self, uncompressed: &[T], samples: usize, sample_length: usize) -> Option<(usize, f64, f64, f64)> {
+        if samples == 0 || sample_length == 0 || uncompressed.len() < 2 * samples * sample_length {
+            return None;
+        }
+
+        let step = (uncompressed.len() - sample_length) / (samples - 1).max(1);
+        let sample_trials = |(_, algo): (usize, &DynCompressor<T>)| -> Vec<Trial> {
+            let mut sample_compressed = Vec::<u8>::new();
+            (0..samples).map(|k| {
+                sample_compressed.clear();
+                let sample = &uncompressed[(k * step)..(k * step + sample_length)];
+                self.trial(algo, sample, &mut sample_compressed, usize::MAX).expect("an unbounded algorithm always finishes")
+            }).collect()
+        };
+
+        // trials[algorithm][sample]
+        let trials: Vec<Vec<Trial>> = if self.parallel {
+            self.install(|| self.algorithms.par_iter().enumerate().map(sample_trials).collect())
+        } else {
+            self.algorithms.iter().enumerate().map(sample_trials).collect()
+        };
+
+        let costs = trials.iter()
+            .map(|trials| trials.iter().map(|trial| self.objective.cost(trial)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let totals = costs.iter().map(|costs| costs.iter().sum::<f64>()).collect::<Vec<_>>();
+        let index = (0..totals.len()).min_by(|a, b| totals[*a].total_cmp(&totals[*b]).then(a.cmp(b)))?;
+        let wins = (0..samples).filter(|k| costs.iter().all(|other| other[*k] >= costs[index][*k])).count();
+
+        let size = trials[index].iter().map(|trial| trial.size).sum::<usize>();
+        let ratio = size as f64 / (samples * sample_length * size_of::<T>()) as f64;
+        Some((index, ratio, totals[index], wins as f64 / samples as f64))
+    }
+
+    // writes the index and output of the picked algorithm, unless it doesn't fit in `limit` bytes
+    fn compress_selected(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> Option<SelectionReport> {
+        assert!(!self.algorithms.is_empty());
         assert!(self.algorithms.len() < 255);
-        let mut best_one = Vec::<u8>::new();
-        let mut shortest_len = usize::MAX;
-        let mut best_one_index = 0u8;
 
-        for (i, algo) in self.algorithms.iter().enumerate() {
-            let mut test_compressed = Vec::<u8>::with_capacity(compressed.capacity());
-            algo.compress(uncompressed, &mut test_compressed);
+        // one byte goes to the index of the algorithm
+        let limit = limit.checked_sub(1)?;
+
+        let sampled = match self.selection {
+            Selection::Exhaustive => None,
+            Selection::Sampled { samples, sample_length, min_confidence } => self.sample(uncompressed, samples, sample_length)
+                .map(|(index, ratio, cost, confidence)| (index, ratio, cost, confidence, confidence >= min_confidence)),
+        };
 
-            if test_compressed.len() < shortest_len {
-                shortest_len = test_compressed.len();
-                best_one = test_compressed;         
-                best_one_index = i as u8;   
-            }
+        if let Some((index, estimated_ratio, cost, confidence, true)) = sampled {
+            compressed.push(index as u8);
+            return self.algorithms[index].compress_bounded(uncompressed, compressed, limit)
+                .then_some(SelectionReport { index, estimated_ratio, confidence, exhaustive: false, cost });
         }
 
-        compressed.push(best_one_index);
+        let (index, best_one, cost) = self.best_candidate(uncompressed, limit)?;
+        compressed.push(index as u8);
         compressed.extend_from_slice(&best_one);
+
+        Some(SelectionReport {
+            index,
+            estimated_ratio: best_one.len() as f64 / size_of_val(uncompressed).max(1) as f64,
+            confidence: sampled.map_or(1.0, |(_, _, _, confidence, _)| confidence),
+            exhaustive: true,
+            cost,
+        })
     }
+}
 
-    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<T>) {
-        assert!(self.algorithms.len() > 0);
-        assert!(self.algorithms.len() < 255);
+impl<T: Pod + PartialEq + Send + Sync> Compressor for Hybrid<T> {
+    type Input = T;
 
-        let best_one_index = compressed[0];
-        let slice = &compressed[1..];
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        self.compress_with_report(uncompressed, compressed);
+    }
 
-        let algo = &self.algorithms[best_one_index as usize];
-        algo.decompress(slice, uncompressed);
+    fn compress_bounded(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        self.compress_selected(uncompressed, compressed, limit).is_some()
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let (&best_one_index, slice) = compressed.split_first().ok_or(CompressionError::Truncated)?;
+
+        let algo = self.algorithms.get(best_one_index as usize).ok_or(CompressionError::UnknownAlgorithm(best_one_index))?;
+        algo.try_decompress_bounded(slice, uncompressed, limit)
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::Hybrid, self.algorithms.iter().map(|algo| algo.descriptor()).collect())
     }
 
     fn new() -> Self {
         Self {
             _phantom: Default::default(),
             algorithms: Vec::new(),
+            selection: Selection::Exhaustive,
+            objective: Objective::Ratio,
+            parallel: true,
+            pool: None,
+            size_bound: false,
         }
     }
 }
-
@@ -0,0 +1,108 @@
+use std::hash::Hash;
+use bytemuck::Pod;
+
+// primitive integers with the wrapping and zigzag helpers the integer codecs need
+pub trait Integer: Pod + Eq + Hash + Send + Sync + 'static {
+    // unsigned integer of the same width, used to store zigzagged residuals
+    type Unsigned: Integer;
+    const BITS: u32;
+
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+
+    // reinterprets the bits as a signed value and maps it so that small magnitudes become small numbers
+    // 0 => 0, -1 => 1, 1 => 2, -2 => 3, ...
+    fn zigzag(self) -> Self::Unsigned;
+    fn unzigzag(value: Self::Unsigned) -> Self;
+
+    // maps the value to an unsigned number with the same ordering, signed values get their sign bit flipped
+    fn to_ordered(self) -> u64;
+    fn from_ordered(value: u64) -> Self;
+}
+
+macro_rules! impl_integer {
+    ($($t:ty => ($unsigned:ty, $signed:ty)),* $(,)?) => {
+        $(
+            impl Integer for $t {
+                type Unsigned = $unsigned;
+                const BITS: u32 = <$t>::BITS;
+
+                fn wrapping_add(self, other: Self) -> Self {
+                    <$t>::wrapping_add(self, other)
+                }
+
+                fn wrapping_sub(self, other: Self) -> Self {
+                    <$t>::wrapping_sub(self, other)
+                }
+
+                fn zigzag(self) -> $unsigned {
+                    let signed = self as $signed;
+                    ((signed << 1) ^ (signed >> (<$t>::BITS - 1))) as $unsigned
+                }
+
+                fn unzigzag(value: $unsigned) -> Self {
+                    (((value >> 1) as $signed) ^ -((value & 1) as $signed)) as $t
+                }
+
+                fn to_ordered(self) -> u64 {
+                    ((self as $unsigned) ^ (<$t>::MIN as $unsigned)) as u64
+                }
+
+                fn from_ordered(value: u64) -> Self {
+                    ((value as $unsigned) ^ (<$t>::MIN as $unsigned)) as $t
+                }
+            }
+        )*
+    };
+}
+
+impl_integer! {
+    u8 => (u8, i8),
+    u16 => (u16, i16),
+    u32 => (u32, i32),
+    u64 => (u64, i64),
+    i8 => (u8, i8),
+    i16 => (u16, i16),
+    i32 => (u32, i32),
+    i64 => (u64, i64),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zigzag_small_magnitudes() {
+        assert_eq!(0i32.zigzag(), 0);
+        assert_eq!((-1i32).zigzag(), 1);
+        assert_eq!(1i32.zigzag(), 2);
+        assert_eq!((-2i32).zigzag(), 3);
+        assert_eq!(u8::MAX.zigzag(), 1);
+    }
+
+    #[test]
+    fn test_zigzag_round_trip_extremes() {
+        for value in [i64::MIN, i64::MAX, 0, -1, 1] {
+            assert_eq!(i64::unzigzag(value.zigzag()), value);
+        }
+
+        for value in [u16::MIN, u16::MAX, 1, 0x8000] {
+            assert_eq!(u16::unzigzag(value.zigzag()), value);
+        }
+    }
+
+    #[test]
+    fn test_ordered_keeps_ordering() {
+        let values = [i32::MIN, -5, -1, 0, 1, i32::MAX];
+        for pair in values.windows(2) {
+            assert!(pair[0].to_ordered() < pair[1].to_ordered());
+        }
+
+        for value in values {
+            assert_eq!(i32::from_ordered(value.to_ordered()), value);
+        }
+
+        assert_eq!(200u8.to_ordered(), 200);
+        assert_eq!(u64::from_ordered(u64::MAX.to_ordered()), u64::MAX);
+    }
+}
@@ -1,7 +1,14 @@
-use std::{collections::HashMap, hash::Hash, io::{BufReader, Read, Write}, marker::PhantomData, ops::Sub};
-use rayon::{iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator}, slice::ParallelSlice, *};
-use bytemuck::{Pod, Zeroable};
+use std::{collections::{HashMap, HashSet}, hash::Hash, marker::PhantomData};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use bytemuck::Pod;
 use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
+use crate::algorithms::bits::{BitReader, BitWriter};
+
+// index mode for indices packed with just enough bits for the largest entry, instead of 1 to 8 whole bytes
+const BIT_PACKED_MODE: u8 = 4;
 
 pub struct Lookup<T: Pod + Eq + Hash + Send + Sync> {
     _phantom: PhantomData<T>,
@@ -11,53 +18,90 @@ pub struct Lookup<T: Pod + Eq + Hash + Send + Sync> {
 impl<T: Pod + Eq + Hash + Send + Sync> Compressor for Lookup<T> {
     type Input = T;
     fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        self.compress_bounded(uncompressed, compressed, usize::MAX);
+    }
+
+    fn compress_bounded(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        let start = compressed.len();
+
+        // nothing to look up, write a header with an empty dictionary
+        if uncompressed.is_empty() {
+            write_count_bytes(1, compressed);
+            write_count_bytes(0, compressed);
+            write_count_bytes(0, compressed);
+            compressed.push(0);
+            return compressed.len() - start <= limit;
+        }
+
+        // every distinct element ends up in the dictionary or before the phase at least once
+        // if there are too many of them to fit, there is no point in searching through all the windows
+        let room = limit / size_of::<T>().max(1);
+        if room < uncompressed.len() {
+            let mut distinct = HashSet::new();
+            for value in uncompressed {
+                if distinct.insert(*value) && distinct.len() > room {
+                    return false;
+                }
+            }
+        }
+
         // check occurences of window of elements with varying slice sizes
         // pick the window size / phase combination that yields the highest occuring members
         let best_of_the_best_across_window_sizes_and_phases = (1..64).into_par_iter().map(|window_size| {
             // check every phase and check for highest occurences in TOTAL
             // the "best phase" will be the one with the highest number of occurences
-            (0..window_size).into_par_iter().map(|phase| {
+            // phases past the end of the data would leave nothing to look up
+            (0..window_size.min(uncompressed.len())).into_par_iter().map(|phase| {
                 let mut occurences = HashMap::<&[T], u32>::new();
 
                 // go through all chunks
                 // make sure to start at phase offset!
-                for (index, window) in uncompressed[phase..].chunks(window_size).enumerate() {
+                for window in uncompressed[phase..].chunks(window_size) {
                     *occurences.entry(window).or_default() += 1;
                 }
 
-                let total_occurences_of_everything = *occurences.values().into_iter().max().unwrap();
+                let total_occurences_of_everything = *occurences.values().max().unwrap();
                 (window_size, phase, total_occurences_of_everything, occurences)
-            }).max_by_key(|(window_size, phase, x, p)| *x).unwrap()
-        }).max_by_key(|(window_size, phase, x, p)| *x).unwrap();
+            }).max_by_key(|(_, _, x, _)| *x).unwrap()
+        }).max_by_key(|(_, _, x, _)| *x).unwrap();
 
         // we have 
         // 1. the best window size to use
         // 2. the best phase for the window size
         // 3. the used lookup hashmap
         // we just need to encode the uncompressed data using these parameters
-        let (window_size, phase, max_occurences, hash_map) = best_of_the_best_across_window_sizes_and_phases;
-        /*
-        dbg!(window_size);
-        dbg!(phase);
-        dbg!(max_occurences);
-        */
+        let (window_size, phase, _, hash_map) = best_of_the_best_across_window_sizes_and_phases;
         
-        compressed.extend_from_slice(&usize::to_ne_bytes(window_size));
-        compressed.extend_from_slice(&usize::to_ne_bytes(phase));
+        // layout:
+        // window size, phase, dictionary entry count
+        // every dictionary entry prefixed by its length (the trailing window might be shorter)
+        // index mode byte, pre-phase elements, one index per window
+        // bit packed indices are prefixed by their width and count, since the padding could be mistaken for an index
+        write_count_bytes(window_size as u64, compressed);
+        write_count_bytes(phase as u64, compressed);
 
         // write the window slices at the very start...       
-        compressed.extend_from_slice(&usize::to_ne_bytes(hash_map.len()));
+        write_count_bytes(hash_map.len() as u64, compressed);
 
         // maps window slices to their indices
         let mut new_hash_map = HashMap::<&[T], usize>::new();
-        for (entry, value) in hash_map {
+        for entry in hash_map.into_keys() {
+            write_count_bytes(entry.len() as u64, compressed);
             compressed.extend_from_slice(bytemuck::cast_slice(entry));    
             new_hash_map.insert(entry, new_hash_map.len()); 
         }
 
+        // go through uncompressed data in chunks, and check from hashmap. we WILL reach a unique value. fosho
+        let should_be_aligned = &uncompressed[phase..];
+        let indices = should_be_aligned.chunks(window_size).map(|slice| new_hash_map[slice] as u64).collect::<Vec<_>>();
+
         // write mode (we can use this to infer how many bytes we will write for each reference to the hashmap)
-        let mode = crate::algorithms::common::get_mode(new_hash_map.len() as u64);
-        compressed.extend_from_slice(&usize::to_ne_bytes(mode));
+        // bit packing wins unless the indices happen to fill whole bytes
+        let mode = get_mode(new_hash_map.len() as u64);
+        let width = u64::BITS - (new_hash_map.len() as u64 - 1).leading_zeros();
+        let packed_size = 2 + (1 << get_mode(indices.len() as u64)) + (indices.len() * width as usize).div_ceil(8);
+        let bit_packed = packed_size < indices.len() << mode;
+        compressed.push(if bit_packed { BIT_PACKED_MODE } else { mode as u8 });
 
         // if phase is not zero, then we need to add the elements that we skipped over at the start... (the one skipped by phase)
         if phase != 0 {
@@ -65,16 +109,104 @@ impl<T: Pod + Eq + Hash + Send + Sync> Compressor for Lookup<T> {
             compressed.extend_from_slice(pre_phase_slice);
         }
 
-        // go through uncompressed data in chunks, and check from hashmap. we WILL reach a unique value. fosho
-        let should_be_aligned = &uncompressed[phase..];
-        for slice in should_be_aligned.chunks(window_size) {
-            let index = new_hash_map[slice];
-            crate::algorithms::common::write_count_bytes_with_mode(index as u64, mode, compressed);
+        if bit_packed {
+            compressed.push(width as u8);
+            write_count_bytes(indices.len() as u64, compressed);
+
+            let mut writer = BitWriter::new(compressed);
+            for index in indices {
+                writer.write_bits(index, width);
+            }
+
+            writer.finish();
+        } else {
+            for index in indices {
+                write_count_bytes_with_mode(index, mode, compressed);
+            }
         }
+
+        compressed.len() - start <= limit
     }
 
-    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<T>) {
-        
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut index = 0;
+        let _window_size = try_read_count(compressed, &mut index)?;
+        let phase = try_read_count(compressed, &mut index)? as usize;
+        let entry_count = try_read_count(compressed, &mut index)? as usize;
+
+        // all the entries are stored back to back, we only keep track of where each one starts and ends
+        let mut entries = Vec::<std::ops::Range<usize>>::new();
+        let mut dictionary = Vec::<T>::new();
+        for _ in 0..entry_count {
+            let length = try_read_count(compressed, &mut index)? as usize;
+            let start = dictionary.len();
+
+            for _ in 0..length {
+                dictionary.push(try_read_value::<T>(compressed, &mut index)?);
+            }
+
+            entries.push(start..dictionary.len());
+        }
+
+        let mode = *compressed.get(index).ok_or(CompressionError::Truncated)?;
+        index += 1;
+
+        check_limit(phase as u64, limit)?;
+        for _ in 0..phase {
+            uncompressed.push(try_read_value::<T>(compressed, &mut index)?);
+        }
+
+        // a single index can stand for a long entry, so the running total is checked before every entry is copied
+        // empty entries count as one element, otherwise width 0 indices could loop without ever reaching the limit
+        let mut decoded = phase as u64;
+        let mut emit = |entry: u64, uncompressed: &mut Vec<T>| -> Result<(), CompressionError> {
+            let range = entries.get(entry as usize).ok_or(CompressionError::UnknownEntry(entry))?;
+            decoded = decoded.saturating_add(range.len().max(1) as u64);
+            check_limit(decoded, limit)?;
+            uncompressed.extend_from_slice(&dictionary[range.clone()]);
+            Ok(())
+        };
+
+        if mode == BIT_PACKED_MODE {
+            let width = *compressed.get(index).ok_or(CompressionError::Truncated)? as u32;
+            index += 1;
+            if width > u64::BITS {
+                return Err(CompressionError::InvalidHeader("index width larger than 64 bits"));
+            }
+
+            let count = try_read_count(compressed, &mut index)? as usize;
+
+            // indices of width 0 take no bits, every one of them is the first entry and only the count says how many there are
+            if width == 0 {
+                let entry_length = entries.first().map_or(0, |range| range.len()).max(1);
+                check_limit((phase as u64).saturating_add((count as u64).saturating_mul(entry_length as u64)), limit)?;
+            }
+
+            let mut reader = BitReader::new(&compressed[index..]);
+            for _ in 0..count {
+                emit(reader.read_bits(width)?, uncompressed)?;
+            }
+
+            index += reader.bytes_consumed();
+            if index != compressed.len() {
+                return Err(CompressionError::TrailingBytes(compressed.len() - index));
+            }
+
+            return Ok(());
+        }
+
+        let mode = mode as usize;
+        while index < compressed.len() {
+            let (entry, bytes_read) = try_read_count_bytes_with_mode(&compressed[index..], mode)?;
+            index += bytes_read;
+            emit(entry, uncompressed)?;
+        }
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::Lookup)
     }
 
     fn new() -> Self {
@@ -0,0 +1,195 @@
+use std::marker::PhantomData;
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
+
+const HASH_BITS: u32 = 16;
+const EMPTY: usize = usize::MAX;
+
+// LZ77 over whole elements, back-references can start at any element and have any length
+// every token is: literal count, literals, match length, and the match offset if the length is not zero
+pub struct LZ<T: Pod + Send + Sync> {
+    // how far back (in elements) a match can start
+    pub window: usize,
+
+    // how many previous occurences of the same hash we try before giving up
+    pub max_chain: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Pod + Send + Sync> LZ<T> {
+    pub fn new_with(window: usize, max_chain: usize) -> Self {
+        Self {
+            window,
+            max_chain,
+            _phantom: Default::default(),
+        }
+    }
+
+    // a match has to save more bytes than its length and offset cost
+    fn min_match() -> usize {
+        8usize.div_ceil(size_of::<T>()).max(2)
+    }
+}
+
+// FNV-1a over the raw bytes of the first `min_match` elements
+fn hash<T: Pod>(window: &[T]) -> usize {
+    let mut hash = 0xcbf29ce484222325u64;
+    for byte in bytemuck::cast_slice::<T, u8>(window) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+
+    (hash >> (64 - HASH_BITS)) as usize
+}
+
+// compares raw bytes so that floats (NaN, -0.0) are matched exactly
+fn same<T: Pod>(a: &T, b: &T) -> bool {
+    bytemuck::bytes_of(a) == bytemuck::bytes_of(b)
+}
+
+fn write_literals<T: Pod>(literals: &[T], compressed: &mut Vec<u8>) {
+    write_count_bytes(literals.len() as u64, compressed);
+    compressed.extend_from_slice(bytemuck::cast_slice(literals));
+}
+
+impl<T: Pod + Send + Sync> Compressor for LZ<T> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        self.compress_bounded(uncompressed, compressed, usize::MAX);
+    }
+
+    fn compress_bounded(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        let start = compressed.len();
+        let min_match = Self::min_match();
+        let n = uncompressed.len();
+
+        // hash chains: `head` is the latest position of every hash, `previous` links to the one before it
+        let mut head = vec![EMPTY; 1 << HASH_BITS];
+        let mut previous = vec![EMPTY; n];
+        let insert = |position: usize, head: &mut [usize], previous: &mut [usize]| {
+            let hash = hash(&uncompressed[position..(position + min_match)]);
+            previous[position] = head[hash];
+            head[hash] = position;
+        };
+
+        let mut literal_start = 0;
+        let mut i = 0;
+
+        while i + min_match <= n {
+            let mut best_length = 0;
+            let mut best_offset = 0;
+            let mut candidate = head[hash(&uncompressed[i..(i + min_match)])];
+            let mut depth = 0;
+
+            while candidate != EMPTY && depth < self.max_chain && i - candidate <= self.window {
+                let length = uncompressed[candidate..].iter()
+                    .zip(&uncompressed[i..])
+                    .take_while(|(a, b)| same(*a, *b))
+                    .count();
+
+                if length > best_length {
+                    best_length = length;
+                    best_offset = i - candidate;
+
+                    if i + length == n {
+                        break;
+                    }
+                }
+
+                candidate = previous[candidate];
+                depth += 1;
+            }
+
+            if best_length >= min_match {
+                write_literals(&uncompressed[literal_start..i], compressed);
+                write_count_bytes(best_length as u64, compressed);
+                write_count_bytes(best_offset as u64, compressed);
+                if compressed.len() - start > limit {
+                    return false;
+                }
+
+                for position in i..(i + best_length).min(n + 1 - min_match) {
+                    insert(position, &mut head, &mut previous);
+                }
+
+                i += best_length;
+                literal_start = i;
+            } else {
+                insert(i, &mut head, &mut previous);
+                i += 1;
+
+                // pending literals get written sooner or later
+                if compressed.len() - start + (i - literal_start) * size_of::<T>() > limit {
+                    return false;
+                }
+            }
+        }
+
+        if literal_start < n {
+            write_literals(&uncompressed[literal_start..], compressed);
+            write_count_bytes(0, compressed);
+        }
+
+        compressed.len() - start <= limit
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        // matches can only point back into what this buffer decoded, not what was already in `uncompressed`
+        let start = uncompressed.len();
+        let mut index = 0;
+
+        // a match length is just a number, so the total is checked before anything is copied
+        let mut decoded = 0u64;
+
+        while index < compressed.len() {
+            let literals = try_read_count(compressed, &mut index)?;
+            decoded = decoded.saturating_add(literals);
+            check_limit(decoded, limit)?;
+            for _ in 0..literals {
+                uncompressed.push(try_read_value::<T>(compressed, &mut index)?);
+            }
+
+            if index == compressed.len() {
+                return Err(CompressionError::Truncated);
+            }
+
+            let length = try_read_count(compressed, &mut index)?;
+            if length == 0 {
+                continue;
+            }
+
+            let offset = try_read_count(compressed, &mut index)?;
+            let available = (uncompressed.len() - start) as u64;
+            if offset == 0 || offset > available {
+                return Err(CompressionError::MatchOutOfRange { offset, available });
+            }
+
+            decoded = decoded.saturating_add(length);
+            check_limit(decoded, limit)?;
+
+            // the match may overlap with what it is producing, it repeats with a period of `offset` so every copy can
+            // take everything from `from` on, which doubles what the next one can take
+            let from = uncompressed.len() - offset as usize;
+            let mut remaining = length as usize;
+            while remaining > 0 {
+                let chunk = remaining.min(uncompressed.len() - from);
+                uncompressed.extend_from_within(from..(from + chunk));
+                remaining -= chunk;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::LZ)
+    }
+
+    fn new() -> Self {
+        Self::new_with(1 << 16, 64)
+    }
+}
@@ -1,7 +1,10 @@
-use std::{io::{BufReader, Read}, marker::PhantomData};
+use std::ops::Range;
 use rayon::{iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator}, slice::ParallelSlice, *};
-use bytemuck::{Pod, Zeroable};
-use crate::compressor::Compressor;
+use crate::compressor::{Compressor, check_limit};
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::try_read_u64;
+use crate::algorithms::count_codec::CountLayout;
 
 pub struct ParChunked<C: Compressor + Send + Sync> {
     pub compressor: C,
@@ -16,11 +19,155 @@ impl<C: Compressor + Send + Sync> ParChunked<C> {
     }
 }
 
-#[derive(Clone, Copy, Pod, Zeroable, Debug)]
-#[repr(C)]
+// `elements` is the number of decompressed elements in the chunk, which lets us seek without decoding
+#[derive(Clone, Copy, Debug)]
 struct ChunkData {
-    offset: usize,
-    count: usize,
+    offset: u64,
+    count: u64,
+    elements: u64,
+}
+
+impl ChunkData {
+    // byte range of the chunk within the chunk data, if it fits in `available` bytes
+    fn range(&self, available: usize) -> Result<Range<usize>, CompressionError> {
+        let error = CompressionError::ChunkOutOfRange { offset: self.offset as usize, count: self.count as usize, available };
+        let start = usize::try_from(self.offset).map_err(|_| error.clone())?;
+        let end = usize::try_from(self.count).ok().and_then(|count| start.checked_add(count)).ok_or(error.clone())?;
+
+        if end > available {
+            return Err(error);
+        }
+
+        Ok(start..end)
+    }
+}
+
+// the table is a flat list of (offset, count, elements) triples, in whichever count layout is the smallest
+// layout: count layout byte, table length in bytes (u64 LE), the encoded table, then the chunk data
+fn write_chunk_table(chunk_table: &[ChunkData], compressed: &mut Vec<u8>) {
+    let counts = chunk_table.iter()
+        .flat_map(|chunk_data| [chunk_data.offset, chunk_data.count, chunk_data.elements])
+        .collect::<Vec<_>>();
+
+    let layout = CountLayout::smallest(&counts);
+    let mut encoded = Vec::<u8>::new();
+    layout.write_counts(&counts, &mut encoded);
+
+    compressed.push(layout as u8);
+    compressed.extend_from_slice(&u64::to_le_bytes(encoded.len() as u64));
+    compressed.extend_from_slice(&encoded);
+}
+
+// validated chunk table: the compressed bytes of every chunk and the first element it decodes to
+struct Chunk {
+    bytes: Range<usize>,
+    first: u64,
+    elements: u64,
+}
+
+fn read_chunk_table(compressed: &[u8]) -> Result<(Vec<Chunk>, &[u8]), CompressionError> {
+    let layout = CountLayout::from_u8(*compressed.first().ok_or(CompressionError::Truncated)?)?;
+    let mut index = 1;
+    let table_length = try_read_u64(compressed, &mut index)?;
+    let end = usize::try_from(table_length).ok().and_then(|length| index.checked_add(length)).ok_or(CompressionError::Truncated)?;
+    let counts = layout.try_read_counts(compressed.get(index..end).ok_or(CompressionError::Truncated)?)?;
+
+    if !counts.len().is_multiple_of(3) {
+        return Err(CompressionError::InvalidHeader("chunk table isn't made of (offset, count, elements) triples"));
+    }
+
+    let chunk_table = counts.chunks_exact(3)
+        .map(|triple| ChunkData { offset: triple[0], count: triple[1], elements: triple[2] })
+        .collect::<Vec<_>>();
+
+    let actual_data_bruh = &compressed[end..];
+    let mut expected_offset = 0;
+    let mut first = 0u64;
+
+    // chunks have to be stored back to back
+    let chunks = chunk_table.iter().map(|chunk_data| {
+        let bytes = chunk_data.range(actual_data_bruh.len())?;
+
+        if bytes.start != expected_offset {
+            return Err(CompressionError::ChunkOutOfRange {
+                offset: bytes.start,
+                count: bytes.len(),
+                available: actual_data_bruh.len()
+            });
+        }
+
+        expected_offset = bytes.end;
+        let chunk = Chunk { bytes, first, elements: chunk_data.elements };
+        first = first.checked_add(chunk_data.elements).ok_or(CompressionError::InvalidHeader("too many elements"))?;
+        Ok(chunk)
+    }).collect::<Result<Vec<_>, _>>()?;
+
+    if expected_offset != actual_data_bruh.len() {
+        return Err(CompressionError::TrailingBytes(actual_data_bruh.len() - expected_offset));
+    }
+
+    Ok((chunks, actual_data_bruh))
+}
+
+impl<C: Compressor + Send + Sync> ParChunked<C> where C::Input: Send + Sync {
+    fn decompress_chunks(&self, chunks: &[Chunk], data: &[u8]) -> Result<Vec<Vec<C::Input>>, CompressionError> {
+        chunks.par_iter().map(|chunk| {
+            const COMPRESSION_FACTOR_HINT: usize = 10;
+
+            let compressed_chunk = &data[chunk.bytes.clone()];
+            let mut local_uncompressed = Vec::<C::Input>::with_capacity(compressed_chunk.len() * COMPRESSION_FACTOR_HINT);
+            // the chunk table says how many elements every chunk holds, so none of them gets to decode more
+            let limit = usize::try_from(chunk.elements).unwrap_or(usize::MAX);
+            self.compressor.try_decompress_bounded(compressed_chunk, &mut local_uncompressed, limit)?;
+
+            if local_uncompressed.len() as u64 != chunk.elements {
+                return Err(CompressionError::LengthMismatch { expected: chunk.elements, actual: local_uncompressed.len() as u64 });
+            }
+
+            Ok(local_uncompressed)
+        }).collect()
+    }
+
+    // total number of elements, read from the chunk table alone
+    pub fn len(&self, compressed: &[u8]) -> Result<u64, CompressionError> {
+        let (chunks, _) = read_chunk_table(compressed)?;
+        Ok(chunks.last().map(|chunk| chunk.first + chunk.elements).unwrap_or_default())
+    }
+
+    // only decompresses the chunks that overlap with `range`
+    pub fn try_decompress_range(&self, compressed: &[u8], range: Range<u64>, uncompressed: &mut Vec<C::Input>) -> Result<(), CompressionError> {
+        let (chunks, data) = read_chunk_table(compressed)?;
+        let len = chunks.last().map(|chunk| chunk.first + chunk.elements).unwrap_or_default();
+
+        if range.start > range.end || range.end > len {
+            return Err(CompressionError::RangeOutOfBounds { start: range.start, end: range.end, len });
+        }
+
+        if range.is_empty() {
+            return Ok(());
+        }
+
+        let first_chunk = chunks.partition_point(|chunk| chunk.first + chunk.elements <= range.start);
+        let last_chunk = chunks.partition_point(|chunk| chunk.first < range.end);
+        let overlapping = &chunks[first_chunk..last_chunk];
+
+        let collected = self.decompress_chunks(overlapping, data)?;
+        let skip = (range.start - overlapping[0].first) as usize;
+        uncompressed.extend(collected.into_iter().flatten().skip(skip).take((range.end - range.start) as usize));
+        Ok(())
+    }
+
+    pub fn decompress_range(&self, compressed: &[u8], range: Range<u64>) -> Result<Vec<C::Input>, CompressionError> {
+        let mut uncompressed = Vec::<C::Input>::new();
+        self.try_decompress_range(compressed, range, &mut uncompressed)?;
+        Ok(uncompressed)
+    }
+
+    pub fn get(&self, compressed: &[u8], index: u64) -> Result<C::Input, CompressionError> {
+        let end = index.checked_add(1).ok_or(CompressionError::RangeOutOfBounds { start: index, end: index, len: 0 })?;
+        let mut uncompressed = self.decompress_range(compressed, index..end)?;
+        Ok(uncompressed.remove(0))
+    }
 }
 
 impl<C: Compressor + Send + Sync> Compressor for ParChunked<C> where C::Input: Send + Sync {
@@ -31,54 +178,39 @@ impl<C: Compressor + Send + Sync> Compressor for ParChunked<C> where C::Input: S
             Some(x) => x,
             None => {                
                 let n_threads = current_num_threads();
-                let chunk_size = (uncompressed.len() + n_threads - 1) / (n_threads / 2);
-                chunk_size
+                uncompressed.len().div_ceil((n_threads / 2).max(1)).max(1)
             },
         };
         
         let collected = uncompressed.par_chunks(chunk_size).map(|chunk| {
-            let mut local_compressed = Vec::<u8>::with_capacity(size_of::<Self::Input>() * chunk.len());
+            let mut local_compressed = Vec::<u8>::with_capacity(size_of_val(chunk));
             self.compressor.compress(chunk, &mut local_compressed);
             local_compressed
         }).collect::<Vec<_>>();
 
-        let mut header = Vec::<ChunkData>::new();
         let mut offset = 0;
-
-        for count in collected.iter().map(|c| c.len()) {
-            header.push(ChunkData { offset, count });
+        let chunk_table = collected.iter().zip(uncompressed.chunks(chunk_size)).map(|(local_compressed, chunk)| {
+            let count = local_compressed.len() as u64;
+            let chunk_data = ChunkData { offset, count, elements: chunk.len() as u64 };
             offset += count;
-        }
+            chunk_data
+        }).collect::<Vec<_>>();
+
+        write_chunk_table(&chunk_table, compressed);
 
-        compressed.extend_from_slice(&usize::to_ne_bytes(header.len()));
-        compressed.extend_from_slice(bytemuck::cast_slice(&header));
         compressed.par_extend(collected.into_par_iter().flatten());
     }
 
-    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<C::Input>) {
-        let mut chunk_count_bytes = [0u8; 8];
-        let mut index = 0;
-        chunk_count_bytes.copy_from_slice(&compressed[index..(index + 8)]);
-        index += 8;
-
-        let chunk_count = usize::from_ne_bytes(chunk_count_bytes);
-        let bytes_to_read = chunk_count * size_of::<ChunkData>();
-        let prefix_sum = bytemuck::cast_slice::<u8, ChunkData>(&compressed[index..(index + bytes_to_read)]);
-        index += bytes_to_read;
-
-        let actual_data_bruh = &compressed[index..];
-
-        let collected = prefix_sum.into_par_iter().map(|chunk_data| {
-            const COMPRESSION_FACTOR_HINT: usize = 10;
-
-            let compressed_chunk = &actual_data_bruh[chunk_data.offset..(chunk_data.count + chunk_data.offset)];
-            let mut local_uncompressed = Vec::<C::Input>::with_capacity(size_of::<Self::Input>() * compressed_chunk.len() * COMPRESSION_FACTOR_HINT);
-            self.compressor.decompress(&compressed_chunk, &mut local_uncompressed);
-            local_uncompressed
-        }).flatten();
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<C::Input>, limit: usize) -> Result<(), CompressionError> {
+        let (chunks, data) = read_chunk_table(compressed)?;
+        check_limit(chunks.last().map(|chunk| chunk.first + chunk.elements).unwrap_or_default(), limit)?;
+        let collected = self.decompress_chunks(&chunks, data)?;
+        uncompressed.par_extend(collected.into_par_iter().flatten());
+        Ok(())
+    }
 
-        
-        uncompressed.par_extend(collected);
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::ParChunked, vec![self.compressor.descriptor()])
     }
     
     fn new() -> Self {
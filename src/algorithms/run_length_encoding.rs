@@ -1,75 +1,72 @@
-use std::{io::{BufReader, Read}, marker::PhantomData};
-use rayon::{iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator}, slice::ParallelSlice, *};
-use bytemuck::{Pod, Zeroable};
+use std::marker::PhantomData;
+use bytemuck::Pod;
 use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
 
-pub struct RLE<T: Pod + PartialEq, C: Compressor<Input = T> = NaiveCompressor<T>> {
+// runs are split into a stream of fixed 8 byte little-endian counts (compressed by `K`) and a stream of values (compressed by `C`)
+pub struct RLE<T: Pod + PartialEq, C: Compressor<Input = T> = NaiveCompressor<T>, K: Compressor<Input = u8> = NaiveCompressor<u8>> {
     compressor: C,
+    count_compressor: K,
     _phantom: PhantomData<T>,
 }
 
-impl<T: Pod + PartialEq, C: Compressor<Input = T>> RLE<T, C> {
+impl<T: Pod + PartialEq, C: Compressor<Input = T>, K: Compressor<Input = u8>> RLE<T, C, K> {
     pub fn new_with(compressor: C) -> Self {
+        Self::new_with_counts(compressor, K::new())
+    }
+
+    pub fn new_with_counts(compressor: C, count_compressor: K) -> Self {
         Self {
             compressor,
+            count_compressor,
             _phantom: Default::default()
         }
     }
 }
 
-impl<T: Pod + PartialEq, C: Compressor<Input = T>> Compressor for RLE<T, C> {
+impl<T: Pod + PartialEq, C: Compressor<Input = T>, K: Compressor<Input = u8>> Compressor for RLE<T, C, K> {
     type Input = T;
     fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {        
-        let mut last = Option::<&T>::None;
-        let mut count = 0u64;
+        let (counts, values) = split_runs(uncompressed);
+        let counts = counts.iter().flat_map(|count| u64::to_le_bytes(*count)).collect::<Vec<u8>>();
+        write_run_streams(&counts, &values, &self.compressor, &self.count_compressor, compressed);
+    }
 
-        for x in uncompressed {
-            if Some(x) == last {
-                count += 1;
-            } else {
-                if let Some(previous) = last.take() {
-                    compressed.extend_from_slice(&u64::to_ne_bytes(count));
-                    compressed.extend_from_slice(bytemuck::bytes_of(previous));
-                    count = 0;    
-                }
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let counts_limit = limit.saturating_mul(size_of::<u64>());
+        let (counts, values) = read_run_streams(compressed, &self.compressor, &self.count_compressor, limit, counts_limit)?;
 
-                last = Some(x);
-                count = 1;
-            }
+        let chunks = counts.chunks_exact(size_of::<u64>());
+        if !chunks.remainder().is_empty() {
+            return Err(CompressionError::Truncated);
         }
 
-        if let Some(previous) = last.take() {
-            compressed.extend_from_slice(&u64::to_ne_bytes(count));
-            compressed.extend_from_slice(bytemuck::bytes_of(previous));
+        if chunks.len() != values.len() {
+            return Err(CompressionError::MismatchedRuns { counts: chunks.len(), values: values.len() });
         }
-    }
 
-    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<T>) {
-        let mut index = 0;
-        
-        while index < compressed.len() {
-            let mut copy = [0u8; 8];
-            copy.copy_from_slice(&compressed[index..(index + 8)]);
-            let count = u64::from_ne_bytes(copy);
-            index += 8;
-            
-            let mut tmp = T::zeroed();
-            let value_bytes = bytemuck::bytes_of_mut(&mut tmp);
-            value_bytes.copy_from_slice(&compressed[index..(index + value_bytes.len())]);
-            index += value_bytes.len();
+        let counts = chunks.map(|count| u64::from_le_bytes(count.try_into().unwrap())).collect::<Vec<_>>();
+        let total = check_run_total(&counts, limit)?;
+        uncompressed.reserve(total as usize);
 
-            for _ in 0..count {
-                uncompressed.push(tmp);
-            }
+        for (count, value) in counts.into_iter().zip(values) {
+            uncompressed.extend(std::iter::repeat_n(value, count as usize));
         }
-        
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::RLE, vec![self.compressor.descriptor(), self.count_compressor.descriptor()])
     }
 
     fn new() -> Self {
         Self {
             compressor: C::new(),
+            count_compressor: K::new(),
             _phantom: Default::default()
         }
     }
 }
-
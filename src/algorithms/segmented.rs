@@ -0,0 +1,156 @@
+use std::{marker::PhantomData, ops::Range};
+use bytemuck::Pod;
+use rayon::{iter::{IntoParallelRefIterator, ParallelIterator}, slice::ParallelSlice};
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor, DynCompressor};
+use crate::algorithms::common::*;
+
+const DEFAULT_BLOCK_SIZE: usize = 1024;
+
+// splits the input wherever the best algorithm changes and compresses every segment with its own algorithm
+// the best algorithm is picked per block of `block_size` elements, neighbouring blocks that agree form one segment
+// and every boundary between segments is then moved to within `block_size / 16` elements of the actual change
+// layout: segment count, then for every segment its algorithm index, element count, compressed length and compressed bytes
+pub struct Segmented<T: Pod + PartialEq + Send + Sync> {
+    pub block_size: usize,
+    algorithms: Vec<DynCompressor<T>>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Pod + PartialEq + Send + Sync> Segmented<T> {
+    pub fn add<C: Compressor<Input = T> + 'static + Send + Sync>(self) -> Self {
+        self.with(Box::new(C::new()))
+    }
+
+    pub fn with(mut self, compressor: DynCompressor<T>) -> Self {
+        self.algorithms.push(compressor);
+        self
+    }
+
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    // the element range and algorithm index of every segment
+    pub fn segments(&self, uncompressed: &[T]) -> Vec<(Range<usize>, usize)> {
+        assert!(!self.algorithms.is_empty());
+        assert!(self.algorithms.len() < 255);
+        assert!(self.block_size > 0, "block size has to be at least 1");
+
+        // smallest output per block, ties go to the algorithm added first
+        let best = uncompressed.par_chunks(self.block_size).map(|block| {
+            let mut block_compressed = Vec::<u8>::new();
+            self.algorithms.iter().enumerate().min_by_key(|(i, algo)| {
+                block_compressed.clear();
+                algo.compress(block, &mut block_compressed);
+                (block_compressed.len(), *i)
+            }).unwrap().0
+        }).collect::<Vec<_>>();
+
+        let mut segments = Vec::<(Range<usize>, usize)>::new();
+        for (block, index) in best.into_iter().enumerate() {
+            let end = ((block + 1) * self.block_size).min(uncompressed.len());
+
+            match segments.last_mut() {
+                Some((range, last)) if *last == index => range.end = end,
+                _ => segments.push((block * self.block_size..end, index)),
+            }
+        }
+
+        for i in 1..segments.len() {
+            let boundary = self.refine_boundary(uncompressed, &segments[i - 1], &segments[i]);
+            segments[i - 1].0.end = boundary;
+            segments[i].0.start = boundary;
+        }
+
+        segments
+    }
+
+    // the split between two neighbouring segments within a block of their current boundary that compresses best
+    fn refine_boundary(&self, uncompressed: &[T], (left, left_index): &(Range<usize>, usize), (right, right_index): &(Range<usize>, usize)) -> usize {
+        let start = left.start.max(left.end.saturating_sub(self.block_size));
+        let end = right.end.min(right.start + self.block_size);
+        let step = (self.block_size / 16).max(1);
+
+        let mut split_compressed = Vec::<u8>::new();
+        let mut size = |index: usize, range: Range<usize>| {
+            split_compressed.clear();
+            self.algorithms[index].compress(&uncompressed[range], &mut split_compressed);
+            split_compressed.len()
+        };
+
+        // both segments keep at least one element
+        (start..=end).step_by(step).chain([left.end])
+            .filter(|split| *split > left.start && *split < right.end)
+            .min_by_key(|split| (size(*left_index, start..*split) + size(*right_index, *split..end), *split))
+            .unwrap()
+    }
+}
+
+impl<T: Pod + PartialEq + Send + Sync> Compressor for Segmented<T> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        let segments = self.segments(uncompressed);
+        let collected = segments.par_iter().map(|(range, index)| {
+            let mut segment_compressed = Vec::<u8>::new();
+            self.algorithms[*index].compress(&uncompressed[range.clone()], &mut segment_compressed);
+            segment_compressed
+        }).collect::<Vec<_>>();
+
+        write_count_bytes(segments.len() as u64, compressed);
+        for ((range, index), segment_compressed) in segments.iter().zip(collected) {
+            compressed.push(*index as u8);
+            write_count_bytes(range.len() as u64, compressed);
+            write_count_bytes(segment_compressed.len() as u64, compressed);
+            compressed.extend_from_slice(&segment_compressed);
+        }
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut index = 0;
+        let mut remaining = limit;
+        let segments = try_read_count(compressed, &mut index)?;
+        for _ in 0..segments {
+            let algorithm = *compressed.get(index).ok_or(CompressionError::Truncated)?;
+            let algo = self.algorithms.get(algorithm as usize).ok_or(CompressionError::UnknownAlgorithm(algorithm))?;
+            index += 1;
+
+            // every segment stores its element count, so it doesn't get to decode any more than that
+            let elements = try_read_count(compressed, &mut index)?;
+            check_limit(elements, remaining)?;
+            remaining -= elements as usize;
+            let length = try_read_count(compressed, &mut index)?;
+            let end = usize::try_from(length).ok().and_then(|length| index.checked_add(length)).ok_or(CompressionError::Truncated)?;
+            let section = compressed.get(index..end).ok_or(CompressionError::Truncated)?;
+            index = end;
+
+            let start = uncompressed.len();
+            algo.try_decompress_bounded(section, uncompressed, elements as usize)?;
+            let actual = (uncompressed.len() - start) as u64;
+            if actual != elements {
+                return Err(CompressionError::LengthMismatch { expected: elements, actual });
+            }
+        }
+
+        if index != compressed.len() {
+            return Err(CompressionError::TrailingBytes(compressed.len() - index));
+        }
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::Segmented, self.algorithms.iter().map(|algo| algo.descriptor()).collect())
+    }
+
+    fn new() -> Self {
+        Self {
+            block_size: DEFAULT_BLOCK_SIZE,
+            algorithms: Vec::new(),
+            _phantom: Default::default(),
+        }
+    }
+}
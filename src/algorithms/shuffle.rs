@@ -0,0 +1,165 @@
+use std::marker::PhantomData;
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+
+// Blosc style byte shuffle: byte 0 of every element, then byte 1 of every element, ...
+// so that bytes which barely change (like the high bytes of small numbers) end up next to each other
+pub fn shuffle_bytes<T: Pod>(elements: &[T], output: &mut Vec<u8>) {
+    let bytes = bytemuck::cast_slice::<T, u8>(elements);
+    let size = size_of::<T>();
+
+    output.reserve(bytes.len());
+    for byte in 0..size {
+        output.extend(bytes.iter().skip(byte).step_by(size));
+    }
+}
+
+pub fn unshuffle_bytes<T: Pod>(shuffled: &[u8], elements: &mut Vec<T>) -> Result<(), CompressionError> {
+    let size = size_of::<T>();
+    if !shuffled.len().is_multiple_of(size) {
+        return Err(CompressionError::Truncated);
+    }
+
+    let count = shuffled.len() / size;
+    let mut bytes = vec![0u8; shuffled.len()];
+    for (byte, plane) in shuffled.chunks_exact(count.max(1)).enumerate() {
+        for (element, value) in plane.iter().enumerate() {
+            bytes[element * size + byte] = *value;
+        }
+    }
+
+    elements.extend(bytes.chunks_exact(size).map(bytemuck::pod_read_unaligned::<T>));
+    Ok(())
+}
+
+// bit shuffle: bit 0 of every element, then bit 1 of every element, ...
+// works on groups of 8 elements so every bit plane fills whole bytes, the last `len % 8` elements are stored as is
+// which keeps the output exactly as long as the input
+pub fn shuffle_bits<T: Pod>(elements: &[T], output: &mut Vec<u8>) {
+    let bytes = bytemuck::cast_slice::<T, u8>(elements);
+    let size = size_of::<T>();
+    let shuffled = elements.len() - elements.len() % 8;
+
+    output.reserve(bytes.len());
+    for bit in 0..(8 * size) {
+        let (byte, shift) = (bit / 8, bit % 8);
+
+        for group in bytes[..(shuffled * size)].chunks_exact(8 * size) {
+            let mut plane = 0u8;
+            for element in 0..8 {
+                plane |= ((group[element * size + byte] >> shift) & 1) << element;
+            }
+
+            output.push(plane);
+        }
+    }
+
+    output.extend_from_slice(&bytes[(shuffled * size)..]);
+}
+
+pub fn unshuffle_bits<T: Pod>(shuffled: &[u8], elements: &mut Vec<T>) -> Result<(), CompressionError> {
+    let size = size_of::<T>();
+    if !shuffled.len().is_multiple_of(size) {
+        return Err(CompressionError::Truncated);
+    }
+
+    let count = shuffled.len() / size;
+    let groups = count / 8;
+    let mut bytes = vec![0u8; shuffled.len()];
+
+    for bit in 0..(8 * size) {
+        let (byte, shift) = (bit / 8, bit % 8);
+
+        for group in 0..groups {
+            let plane = shuffled[bit * groups + group];
+            for element in 0..8 {
+                bytes[(group * 8 + element) * size + byte] |= ((plane >> element) & 1) << shift;
+            }
+        }
+    }
+
+    let tail = groups * 8 * size;
+    bytes[tail..].copy_from_slice(&shuffled[tail..]);
+    elements.extend(bytes.chunks_exact(size).map(bytemuck::pod_read_unaligned::<T>));
+    Ok(())
+}
+
+// byte shuffles the elements and hands the bytes to the inner compressor
+pub struct Shuffle<T: Pod, C: Compressor<Input = u8> = NaiveCompressor<u8>> {
+    compressor: C,
+    _phantom: PhantomData<T>,
+}
+
+// bit shuffles the elements and hands the bytes to the inner compressor
+pub struct BitShuffle<T: Pod, C: Compressor<Input = u8> = NaiveCompressor<u8>> {
+    compressor: C,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Pod, C: Compressor<Input = u8>> Shuffle<T, C> {
+    pub fn new_with(compressor: C) -> Self {
+        Self {
+            compressor,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<T: Pod, C: Compressor<Input = u8>> BitShuffle<T, C> {
+    pub fn new_with(compressor: C) -> Self {
+        Self {
+            compressor,
+            _phantom: Default::default(),
+        }
+    }
+}
+
+impl<T: Pod, C: Compressor<Input = u8>> Compressor for Shuffle<T, C> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        let mut shuffled = Vec::<u8>::new();
+        shuffle_bytes(uncompressed, &mut shuffled);
+        self.compressor.compress(&shuffled, compressed);
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut shuffled = Vec::<u8>::new();
+        self.compressor.try_decompress_bounded(compressed, &mut shuffled, limit.saturating_mul(size_of::<T>()))?;
+        unshuffle_bytes(&shuffled, uncompressed)
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::Shuffle, vec![self.compressor.descriptor()])
+    }
+
+    fn new() -> Self {
+        Self::new_with(C::new())
+    }
+}
+
+impl<T: Pod, C: Compressor<Input = u8>> Compressor for BitShuffle<T, C> {
+    type Input = T;
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        let mut shuffled = Vec::<u8>::new();
+        shuffle_bits(uncompressed, &mut shuffled);
+        self.compressor.compress(&shuffled, compressed);
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut shuffled = Vec::<u8>::new();
+        self.compressor.try_decompress_bounded(compressed, &mut shuffled, limit.saturating_mul(size_of::<T>()))?;
+        unshuffle_bits(&shuffled, uncompressed)
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::BitShuffle, vec![self.compressor.descriptor()])
+    }
+
+    fn new() -> Self {
+        Self::new_with(C::new())
+    }
+}
@@ -1,73 +1,81 @@
-use std::{io::{BufReader, Read, Write}, marker::PhantomData};
-use rayon::{iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator}, slice::ParallelSlice, *};
-use bytemuck::{Pod, Zeroable};
+use std::marker::PhantomData;
+use bytemuck::Pod;
 use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::common::*;
+use crate::algorithms::count_codec::CountLayout;
 
-pub struct VRLE<T: Pod + PartialEq, C: Compressor<Input = T> = NaiveCompressor<T>> {
+// same as RLE, but the counts are variable length so short runs only take up 2 bytes (or a few bits)
+// layout: count layout byte, then the run streams
+pub struct VRLE<T: Pod + PartialEq, C: Compressor<Input = T> = NaiveCompressor<T>, K: Compressor<Input = u8> = NaiveCompressor<u8>> {
     compressor: C,
+    count_compressor: K,
+    pub layout: CountLayout,
     _phantom: PhantomData<T>,
 }
 
-impl<T: Pod + PartialEq, C: Compressor<Input = T>> VRLE<T, C> {
+impl<T: Pod + PartialEq, C: Compressor<Input = T>, K: Compressor<Input = u8>> VRLE<T, C, K> {
     pub fn new_with(compressor: C) -> Self {
+        Self::new_with_counts(compressor, K::new())
+    }
+
+    pub fn new_with_counts(compressor: C, count_compressor: K) -> Self {
         Self {
             compressor,
+            count_compressor,
+            layout: CountLayout::ModeBytes,
             _phantom: Default::default()
         }
     }
+
+    pub fn with_layout(mut self, layout: CountLayout) -> Self {
+        self.layout = layout;
+        self
+    }
 }
 
-impl<T: Pod + PartialEq, C: Compressor<Input = T>> Compressor for VRLE<T, C> {
+impl<T: Pod + PartialEq, C: Compressor<Input = T>, K: Compressor<Input = u8>> Compressor for VRLE<T, C, K> {
     type Input = T;
     fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {        
-        let mut last = Option::<&T>::None;
-        let mut count = 0u64;
+        let (counts, values) = split_runs(uncompressed);
 
-        for x in uncompressed {
-            if Some(x) == last {
-                count += 1;
-            } else {
-                if let Some(previous) = last.take() {
-                    crate::algorithms::common::write_count_bytes(count, compressed);
-                    compressed.extend_from_slice(bytemuck::bytes_of(previous));
-                    count = 0;    
-                }
+        let mut encoded_counts = Vec::<u8>::with_capacity(counts.len() * 2);
+        self.layout.write_counts(&counts, &mut encoded_counts);
 
-                last = Some(x);
-                count = 1;
-            }
-        }
+        compressed.push(self.layout as u8);
+        write_run_streams(&encoded_counts, &values, &self.compressor, &self.count_compressor, compressed);
+    }
 
-        if let Some(previous) = last.take() {
-            crate::algorithms::common::write_count_bytes(count, compressed);
-            compressed.extend_from_slice(bytemuck::bytes_of(previous));
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let layout = CountLayout::from_u8(*compressed.first().ok_or(CompressionError::Truncated)?)?;
+        let (encoded_counts, values) = read_run_streams(&compressed[1..], &self.compressor, &self.count_compressor, limit, layout.max_length(limit))?;
+        let counts = layout.try_read_counts(&encoded_counts)?;
+
+        if counts.len() != values.len() {
+            return Err(CompressionError::MismatchedRuns { counts: counts.len(), values: values.len() });
         }
-    }
 
-    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<T>) {
-        let mut index = 0;
-        
-        while index < compressed.len() {
-            let (count, bytes_read) = crate::algorithms::common::read_count_bytes(&compressed[index..]);
-            index += bytes_read;
-            
-            let mut tmp = T::zeroed();
-            let value_bytes = bytemuck::bytes_of_mut(&mut tmp);
-            value_bytes.copy_from_slice(&compressed[index..(index + value_bytes.len())]);
-            index += value_bytes.len();
+        let total = check_run_total(&counts, limit)?;
+        uncompressed.reserve(total as usize);
 
-            for _ in 0..count {
-                uncompressed.push(tmp);
-            }
+        for (count, value) in counts.into_iter().zip(values) {
+            uncompressed.extend(std::iter::repeat_n(value, count as usize));
         }
-        
+
+        Ok(())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::VRLE, vec![self.compressor.descriptor(), self.count_compressor.descriptor()])
     }
 
     fn new() -> Self {
         Self {
             compressor: C::new(),
+            count_compressor: K::new(),
+            layout: CountLayout::ModeBytes,
             _phantom: Default::default()
         }
     }
 }
-
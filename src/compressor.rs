@@ -1,13 +1,56 @@
-use std::{io::{BufReader, Read}, marker::PhantomData};
-use rayon::{iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator}, slice::ParallelSlice, *};
+use std::marker::PhantomData;
 use bytemuck::{Pod, Zeroable};
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
 
 
 pub trait Compressor {
     type Input;
     fn new() -> Self where Self: Sized;
     fn compress(&self, uncompressed: &[Self::Input], compressed: &mut Vec<u8>);
-    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<Self::Input>);
+
+    // validates the compressed buffer while decoding so that untrusted input can be rejected
+    // fails with `OutputTooLarge` before appending more than `limit` elements, runs and matches are checked before
+    // anything is allocated for them, so a few bytes can't ask for more memory than the caller is willing to spend
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<Self::Input>, limit: usize) -> Result<(), CompressionError>;
+
+    // `try_decompress_bounded` up to the most elements a `Vec` can hold
+    // pass a real limit (like the element count of a framed header) when the buffer comes from somewhere untrusted
+    fn try_decompress(&self, compressed: &[u8], uncompressed: &mut Vec<Self::Input>) -> Result<(), CompressionError> {
+        self.try_decompress_bounded(compressed, uncompressed, max_elements::<Self::Input>())
+    }
+
+    // tree of algorithm ids describing this compressor and its inner compressors, stored in framed headers
+    fn descriptor(&self) -> Descriptor;
+
+    // like `compress`, but may give up as soon as the output gets longer than `limit` bytes
+    // returns whether it finished, the contents of `compressed` are unspecified when it didn't
+    fn compress_bounded(&self, uncompressed: &[Self::Input], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        let start = compressed.len();
+        self.compress(uncompressed, compressed);
+        compressed.len() - start <= limit
+    }
+
+    // panicking shorthand for when the buffer is known to come from `compress`
+    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<Self::Input>) {
+        if let Err(err) = self.try_decompress(compressed, uncompressed) {
+            panic!("failed to decompress: {err}");
+        }
+    }
+}
+
+// the most elements of `T` a `Vec` can hold
+pub fn max_elements<T>() -> usize {
+    isize::MAX as usize / size_of::<T>().max(1)
+}
+
+// fails unless `elements` more elements fit in `limit`
+pub fn check_limit(elements: u64, limit: usize) -> Result<(), CompressionError> {
+    if elements > limit as u64 {
+        return Err(CompressionError::OutputTooLarge { requested: elements, limit: limit as u64 });
+    }
+
+    Ok(())
 }
 
 pub struct NaiveCompressor<T> {
@@ -21,10 +64,31 @@ impl<T: Pod + Zeroable> Compressor for NaiveCompressor<T> {
     }
 
     fn compress(&self, uncompressed: &[Self::Input], compressed: &mut Vec<u8>) {
-        compressed.copy_from_slice(bytemuck::cast_slice(uncompressed));
+        compressed.extend_from_slice(bytemuck::cast_slice(uncompressed));
     }
 
-    fn decompress(&self, compressed: &[u8], uncompressed: &mut Vec<Self::Input>) {
-        uncompressed.copy_from_slice(bytemuck::cast_slice(compressed));
+    fn compress_bounded(&self, uncompressed: &[Self::Input], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        if size_of_val(uncompressed) > limit {
+            return false;
+        }
+
+        self.compress(uncompressed, compressed);
+        true
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<Self::Input>, limit: usize) -> Result<(), CompressionError> {
+        let chunks = compressed.chunks_exact(size_of::<T>());
+        if !chunks.remainder().is_empty() {
+            return Err(CompressionError::TrailingBytes(chunks.remainder().len()));
+        }
+
+        check_limit(chunks.len() as u64, limit)?;
+
+        uncompressed.extend(chunks.map(bytemuck::pod_read_unaligned::<T>));
+        Ok(())
     }
-}
\ No newline at end of file
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::Naive)
+    }
+}
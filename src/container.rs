@@ -0,0 +1,235 @@
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::descriptor::*;
+use crate::algorithms::Integer;
+use crate::error::CompressionError;
+
+// framed layout:
+// magic (4 bytes), version, flags, element size, element count (u64 LE), algorithm tree, payload
+pub const MAGIC: [u8; 4] = *b"CEXP";
+pub const VERSION: u8 = 1;
+
+// bits of the flags byte, together with the element size they tell us how to interpret the elements
+pub const FLAG_SIGNED: u8 = 1 << 0;
+pub const FLAG_FLOAT: u8 = 1 << 1;
+const KNOWN_FLAGS: u8 = FLAG_SIGNED | FLAG_FLOAT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementKind {
+    U8, U16, U32, U64,
+    I8, I16, I32, I64,
+    F32, F64,
+}
+
+impl ElementKind {
+    pub fn size(self) -> usize {
+        match self {
+            ElementKind::U8 | ElementKind::I8 => 1,
+            ElementKind::U16 | ElementKind::I16 => 2,
+            ElementKind::U32 | ElementKind::I32 | ElementKind::F32 => 4,
+            ElementKind::U64 | ElementKind::I64 | ElementKind::F64 => 8,
+        }
+    }
+
+    pub fn flags(self) -> u8 {
+        match self {
+            ElementKind::I8 | ElementKind::I16 | ElementKind::I32 | ElementKind::I64 => FLAG_SIGNED,
+            ElementKind::F32 | ElementKind::F64 => FLAG_FLOAT | FLAG_SIGNED,
+            _ => 0,
+        }
+    }
+
+    pub fn from_parts(size: u8, flags: u8) -> Result<Self, CompressionError> {
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(CompressionError::InvalidHeader("unknown flags"));
+        }
+
+        let kind = match (size, flags) {
+            (1, 0) => ElementKind::U8,
+            (2, 0) => ElementKind::U16,
+            (4, 0) => ElementKind::U32,
+            (8, 0) => ElementKind::U64,
+            (1, FLAG_SIGNED) => ElementKind::I8,
+            (2, FLAG_SIGNED) => ElementKind::I16,
+            (4, FLAG_SIGNED) => ElementKind::I32,
+            (8, FLAG_SIGNED) => ElementKind::I64,
+            (4, _) => ElementKind::F32,
+            (8, _) => ElementKind::F64,
+            _ => return Err(CompressionError::InvalidHeader("unsupported element type")),
+        };
+
+        Ok(kind)
+    }
+}
+
+// primitive types that know their own element kind
+pub trait Primitive: Pod {
+    const KIND: ElementKind;
+}
+
+macro_rules! impl_primitive {
+    ($($t:ty => $kind:ident),* $(,)?) => {
+        $(
+            impl Primitive for $t {
+                const KIND: ElementKind = ElementKind::$kind;
+            }
+        )*
+    };
+}
+
+impl_primitive! {
+    u8 => U8, u16 => U16, u32 => U32, u64 => U64,
+    i8 => I8, i16 => I16, i32 => I32, i64 => I64,
+    f32 => F32, f64 => F64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub kind: ElementKind,
+    pub count: u64,
+    pub descriptor: Descriptor,
+}
+
+impl Header {
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&MAGIC);
+        buffer.push(self.version);
+        buffer.push(self.kind.flags());
+        buffer.push(self.kind.size() as u8);
+        buffer.extend_from_slice(&u64::to_le_bytes(self.count));
+        self.descriptor.write(buffer);
+    }
+
+    // the payload may not decode to more elements than the header says it holds
+    pub fn limit(&self) -> usize {
+        usize::try_from(self.count).unwrap_or(usize::MAX)
+    }
+
+    // returns the header along with the offset of the payload
+    pub fn try_read(framed: &[u8]) -> Result<(Self, usize), CompressionError> {
+        let fixed = framed.get(..15).ok_or(CompressionError::Truncated)?;
+
+        if fixed[0..4] != MAGIC {
+            return Err(CompressionError::InvalidHeader("bad magic number"));
+        }
+
+        let version = fixed[4];
+        if version != VERSION {
+            return Err(CompressionError::InvalidHeader("unsupported format version"));
+        }
+
+        let kind = ElementKind::from_parts(fixed[6], fixed[5])?;
+        let count = u64::from_le_bytes(fixed[7..15].try_into().unwrap());
+
+        let mut index = 15;
+        let descriptor = Descriptor::try_read(framed, &mut index)?;
+        Ok((Self { version, kind, count, descriptor }, index))
+    }
+}
+
+// compresses with a header in front, so that `decode_any` can decode it without knowing the compressor type
+pub fn frame<C: Compressor>(compressor: &C, kind: ElementKind, uncompressed: &[C::Input], framed: &mut Vec<u8>) {
+    assert_eq!(size_of::<C::Input>(), kind.size());
+
+    let header = Header {
+        version: VERSION,
+        kind,
+        count: uncompressed.len() as u64,
+        descriptor: compressor.descriptor(),
+    };
+
+    header.write(framed);
+    compressor.compress(uncompressed, framed);
+}
+
+// decodes a framed buffer with a known compressor, checking that the header matches it
+pub fn unframe<C: Compressor>(compressor: &C, framed: &[u8], uncompressed: &mut Vec<C::Input>) -> Result<Header, CompressionError> {
+    let (header, offset) = Header::try_read(framed)?;
+
+    if header.kind.size() != size_of::<C::Input>() {
+        return Err(CompressionError::InvalidHeader("element size does not match the compressor"));
+    }
+
+    if header.descriptor != compressor.descriptor() {
+        return Err(CompressionError::InvalidHeader("algorithm tree does not match the compressor"));
+    }
+
+    let start = uncompressed.len();
+    compressor.try_decompress_bounded(&framed[offset..], uncompressed, header.limit())?;
+    check_count(&header, (uncompressed.len() - start) as u64)?;
+    Ok(header)
+}
+
+fn check_count(header: &Header, actual: u64) -> Result<(), CompressionError> {
+    if header.count != actual {
+        return Err(CompressionError::LengthMismatch { expected: header.count, actual });
+    }
+
+    Ok(())
+}
+
+// decoded elements, stored as the unsigned integer of the same width as the original type
+// use `Header::kind` to know how to reinterpret them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Elements {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    U64(Vec<u64>),
+}
+
+impl Elements {
+    pub fn len(&self) -> usize {
+        match self {
+            Elements::U8(elements) => elements.len(),
+            Elements::U16(elements) => elements.len(),
+            Elements::U32(elements) => elements.len(),
+            Elements::U64(elements) => elements.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Elements::U8(elements) => elements,
+            Elements::U16(elements) => bytemuck::cast_slice(elements),
+            Elements::U32(elements) => bytemuck::cast_slice(elements),
+            Elements::U64(elements) => bytemuck::cast_slice(elements),
+        }
+    }
+}
+
+fn decode_with<T: Integer>(header: &Header, payload: &[u8]) -> Result<Vec<T>, CompressionError> {
+    let compressor = build::<T>(&header.descriptor)?;
+    let mut uncompressed = Vec::<T>::new();
+    compressor.try_decompress_bounded(payload, &mut uncompressed, header.limit())?;
+    check_count(header, uncompressed.len() as u64)?;
+    Ok(uncompressed)
+}
+
+// rebuilds the decoder from the header and decodes the payload
+pub fn decode_any(framed: &[u8]) -> Result<(Header, Elements), CompressionError> {
+    let (header, offset) = Header::try_read(framed)?;
+    let payload = &framed[offset..];
+
+    // codecs like BitPack order signed values differently from unsigned ones, so signed kinds decode with their own
+    // type and are reinterpreted afterwards, floats only ever go through bit exact codecs
+    let elements = match header.kind {
+        ElementKind::I8 => Elements::U8(decode_with::<i8>(&header, payload)?.into_iter().map(|value| value as u8).collect()),
+        ElementKind::I16 => Elements::U16(decode_with::<i16>(&header, payload)?.into_iter().map(|value| value as u16).collect()),
+        ElementKind::I32 => Elements::U32(decode_with::<i32>(&header, payload)?.into_iter().map(|value| value as u32).collect()),
+        ElementKind::I64 => Elements::U64(decode_with::<i64>(&header, payload)?.into_iter().map(|value| value as u64).collect()),
+        _ => match header.kind.size() {
+            1 => Elements::U8(decode_with(&header, payload)?),
+            2 => Elements::U16(decode_with(&header, payload)?),
+            4 => Elements::U32(decode_with(&header, payload)?),
+            _ => Elements::U64(decode_with(&header, payload)?),
+        },
+    };
+
+    Ok((header, elements))
+}
@@ -0,0 +1,292 @@
+use std::marker::PhantomData;
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::algorithms::*;
+use crate::error::CompressionError;
+
+// boxed compressor used whenever the algorithm stack is only known at runtime
+pub type DynCompressor<T> = Box<dyn Compressor<Input = T> + Send + Sync>;
+
+// stable identifiers stored in framed headers, never reorder these
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlgorithmId {
+    Naive = 0,
+    RLE = 1,
+    VRLE = 2,
+    ParChunked = 3,
+    Hybrid = 4,
+    Lookup = 5,
+    Delta = 6,
+    Pipeline = 7,
+    Identity = 8,
+    Chain = 9,
+    Bytes = 10,
+    Differences = 11,
+    Encoded = 12,
+    LZ = 13,
+    Huffman = 14,
+    ArithmeticOrder0 = 15,
+    ArithmeticOrder1 = 16,
+    BitPack = 17,
+    PFor = 18,
+    Gorilla = 19,
+    DeltaOfDelta = 20,
+    Buckets = 21,
+    Shuffle = 22,
+    BitShuffle = 23,
+    ShuffledBytes = 24,
+    ShuffledBits = 25,
+    Columnar = 26,
+    Segmented = 27,
+}
+
+impl AlgorithmId {
+    pub const ALL: [AlgorithmId; 28] = [
+        AlgorithmId::Naive, AlgorithmId::RLE, AlgorithmId::VRLE, AlgorithmId::ParChunked,
+        AlgorithmId::Hybrid, AlgorithmId::Lookup, AlgorithmId::Delta, AlgorithmId::Pipeline,
+        AlgorithmId::Identity, AlgorithmId::Chain, AlgorithmId::Bytes, AlgorithmId::Differences,
+        AlgorithmId::Encoded, AlgorithmId::LZ, AlgorithmId::Huffman, AlgorithmId::ArithmeticOrder0,
+        AlgorithmId::ArithmeticOrder1, AlgorithmId::BitPack, AlgorithmId::PFor,
+        AlgorithmId::Gorilla, AlgorithmId::DeltaOfDelta, AlgorithmId::Buckets, AlgorithmId::Shuffle,
+        AlgorithmId::BitShuffle, AlgorithmId::ShuffledBytes, AlgorithmId::ShuffledBits,
+        AlgorithmId::Columnar, AlgorithmId::Segmented,
+    ];
+
+    pub fn from_u8(id: u8) -> Result<Self, CompressionError> {
+        Self::ALL.get(id as usize).copied().ok_or(CompressionError::UnsupportedAlgorithm(id))
+    }
+}
+
+// deepest descriptor tree we accept when reading, so corrupted headers can't blow the stack
+pub const MAX_DEPTH: usize = 32;
+
+// tree of the algorithms (and their inner compressors) that produced a buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Descriptor {
+    pub algorithm: AlgorithmId,
+    pub children: Vec<Descriptor>,
+}
+
+impl Descriptor {
+    pub fn leaf(algorithm: AlgorithmId) -> Self {
+        Self { algorithm, children: Vec::new() }
+    }
+
+    pub fn node(algorithm: AlgorithmId, children: Vec<Descriptor>) -> Self {
+        Self { algorithm, children }
+    }
+
+    // id byte, child count byte, then every child in order
+    pub fn write(&self, buffer: &mut Vec<u8>) {
+        assert!(self.children.len() <= u8::MAX as usize);
+        buffer.push(self.algorithm as u8);
+        buffer.push(self.children.len() as u8);
+
+        for child in self.children.iter() {
+            child.write(buffer);
+        }
+    }
+
+    pub fn try_read(buffer: &[u8], index: &mut usize) -> Result<Self, CompressionError> {
+        Self::try_read_with_depth(buffer, index, 0)
+    }
+
+    fn try_read_with_depth(buffer: &[u8], index: &mut usize, depth: usize) -> Result<Self, CompressionError> {
+        if depth > MAX_DEPTH {
+            return Err(CompressionError::InvalidHeader("algorithm tree is too deep"));
+        }
+
+        let bytes = buffer.get(*index..(*index + 2)).ok_or(CompressionError::Truncated)?;
+        let algorithm = AlgorithmId::from_u8(bytes[0])?;
+        let child_count = bytes[1] as usize;
+        *index += 2;
+
+        let children = (0..child_count)
+            .map(|_| Self::try_read_with_depth(buffer, index, depth + 1))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { algorithm, children })
+    }
+
+    fn expect_children(&self, count: usize) -> Result<(), CompressionError> {
+        if self.children.len() != count {
+            return Err(CompressionError::InvalidHeader("wrong number of inner algorithms"));
+        }
+
+        Ok(())
+    }
+}
+
+// reconstructs the compressor that `descriptor` describes
+// pipelines change element types between stages, so they can only be decoded through their concrete type
+pub fn build<T: Integer>(descriptor: &Descriptor) -> Result<DynCompressor<T>, CompressionError> {
+    let children = &descriptor.children;
+
+    let compressor: DynCompressor<T> = match descriptor.algorithm {
+        AlgorithmId::Naive => {
+            descriptor.expect_children(0)?;
+            Box::new(NaiveCompressor::<T>::new())
+        },
+        AlgorithmId::RLE => {
+            descriptor.expect_children(2)?;
+            Box::new(RLE::new_with_counts(build::<T>(&children[0])?, build::<u8>(&children[1])?))
+        },
+        AlgorithmId::VRLE => {
+            descriptor.expect_children(2)?;
+            Box::new(VRLE::new_with_counts(build::<T>(&children[0])?, build::<u8>(&children[1])?))
+        },
+        AlgorithmId::ParChunked => {
+            descriptor.expect_children(1)?;
+            Box::new(ParChunked::new_with(build::<T>(&children[0])?, None))
+        },
+        AlgorithmId::Hybrid => {
+            if children.is_empty() {
+                return Err(CompressionError::InvalidHeader("no inner algorithms"));
+            }
+
+            let mut hybrid = Hybrid::<T>::new();
+            for child in children.iter() {
+                hybrid = hybrid.with(build::<T>(child)?);
+            }
+            Box::new(hybrid)
+        },
+        AlgorithmId::Segmented => {
+            if children.is_empty() {
+                return Err(CompressionError::InvalidHeader("no inner algorithms"));
+            }
+
+            let mut segmented = Segmented::<T>::new();
+            for child in children.iter() {
+                segmented = segmented.with(build::<T>(child)?);
+            }
+            Box::new(segmented)
+        },
+        AlgorithmId::Lookup => {
+            descriptor.expect_children(0)?;
+            Box::new(Lookup::<T>::new())
+        },
+        AlgorithmId::Delta => {
+            descriptor.expect_children(1)?;
+            Box::new(Delta::<T, _>::new_with(build::<T::Unsigned>(&children[0])?))
+        },
+        AlgorithmId::LZ => {
+            descriptor.expect_children(0)?;
+            Box::new(LZ::<T>::new())
+        },
+        AlgorithmId::Huffman => {
+            descriptor.expect_children(0)?;
+            Box::new(Huffman::<T>::new())
+        },
+        AlgorithmId::ArithmeticOrder0 => {
+            descriptor.expect_children(0)?;
+            Box::new(Arithmetic::<T, Order0>::new())
+        },
+        AlgorithmId::ArithmeticOrder1 => {
+            descriptor.expect_children(0)?;
+            Box::new(Arithmetic::<T, Order1>::new())
+        },
+        AlgorithmId::BitPack => {
+            descriptor.expect_children(0)?;
+            Box::new(BitPack::<T>::new())
+        },
+        AlgorithmId::PFor => {
+            descriptor.expect_children(0)?;
+            Box::new(PFor::<T>::new())
+        },
+        AlgorithmId::Gorilla => {
+            descriptor.expect_children(0)?;
+            match size_of::<T>() {
+                4 => Box::new(Reinterpret::<T, _>::new_with(Gorilla::<u32>::new())),
+                8 => Box::new(Reinterpret::<T, _>::new_with(Gorilla::<u64>::new())),
+                _ => return Err(CompressionError::UnsupportedAlgorithm(AlgorithmId::Gorilla as u8)),
+            }
+        },
+        AlgorithmId::DeltaOfDelta => {
+            descriptor.expect_children(1)?;
+            Box::new(DeltaOfDelta::<T, _>::new_with(build::<T::Unsigned>(&children[0])?))
+        },
+        AlgorithmId::Buckets => {
+            descriptor.expect_children(0)?;
+            Box::new(Buckets::<T>::new())
+        },
+        AlgorithmId::Shuffle => {
+            descriptor.expect_children(1)?;
+            Box::new(Shuffle::<T, _>::new_with(build::<u8>(&children[0])?))
+        },
+        AlgorithmId::BitShuffle => {
+            descriptor.expect_children(1)?;
+            Box::new(BitShuffle::<T, _>::new_with(build::<u8>(&children[0])?))
+        },
+        other => return Err(CompressionError::UnsupportedAlgorithm(other as u8)),
+    };
+
+    Ok(compressor)
+}
+
+// hands the elements to `C` as another type of the same size, for codecs that only care about the bits
+// like Gorilla, which is only implemented for some of the types `build` has to cover
+struct Reinterpret<T: Pod, C: Compressor> {
+    compressor: C,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Pod, C: Compressor> Reinterpret<T, C> where C::Input: Pod {
+    fn new_with(compressor: C) -> Self {
+        assert_eq!(size_of::<T>(), size_of::<C::Input>());
+        Self { compressor, _phantom: Default::default() }
+    }
+}
+
+impl<T: Pod, C: Compressor> Compressor for Reinterpret<T, C> where C::Input: Pod {
+    type Input = T;
+
+    fn new() -> Self {
+        Self::new_with(C::new())
+    }
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        self.compressor.compress(bytemuck::cast_slice(uncompressed), compressed);
+    }
+
+    fn compress_bounded(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        self.compressor.compress_bounded(bytemuck::cast_slice(uncompressed), compressed, limit)
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        let mut values = Vec::new();
+        self.compressor.try_decompress_bounded(compressed, &mut values, limit)?;
+        uncompressed.extend_from_slice(bytemuck::cast_slice(&values));
+        Ok(())
+    }
+
+    // invisible in headers, decoding builds it again from the element type
+    fn descriptor(&self) -> Descriptor {
+        self.compressor.descriptor()
+    }
+}
+
+impl<T: Pod + Send + Sync> Compressor for DynCompressor<T> {
+    type Input = T;
+
+    // a dynamic compressor has no natural default, fall back to storing the elements as they are
+    fn new() -> Self {
+        Box::new(NaiveCompressor::<T>::new())
+    }
+
+    fn compress(&self, uncompressed: &[T], compressed: &mut Vec<u8>) {
+        self.as_ref().compress(uncompressed, compressed);
+    }
+
+    fn compress_bounded(&self, uncompressed: &[T], compressed: &mut Vec<u8>, limit: usize) -> bool {
+        self.as_ref().compress_bounded(uncompressed, compressed, limit)
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        self.as_ref().try_decompress_bounded(compressed, uncompressed, limit)
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        self.as_ref().descriptor()
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CompressionError {
+    // the buffer ended before a complete header, count or value could be read
+    Truncated,
+
+    // a count was prefixed with a mode byte that no encoder ever writes
+    UnknownMode(u8),
+
+    // the Hybrid index byte points past the registered algorithms
+    UnknownAlgorithm(u8),
+
+    // a ParChunked chunk table entry points outside of the chunk data
+    ChunkOutOfRange { offset: usize, count: usize, available: usize },
+
+    // a Lookup index points past the end of the dictionary
+    UnknownEntry(u64),
+
+    // RLE / VRLE decoded a different number of run counts and run values
+    MismatchedRuns { counts: usize, values: usize },
+
+    // a framed header or algorithm tree is malformed
+    InvalidHeader(&'static str),
+
+    // the algorithm id is unknown, or cannot be constructed at runtime
+    UnsupportedAlgorithm(u8),
+
+    // the framed header promised a different number of elements than what was decoded
+    LengthMismatch { expected: u64, actual: u64 },
+
+    // a random access request reaches past the end of the compressed elements
+    RangeOutOfBounds { start: u64, end: u64, len: u64 },
+
+    // an LZ back-reference points before the start of the decoded elements
+    MatchOutOfRange { offset: u64, available: u64 },
+
+    // an entropy coded bitstream contains a code (or code table) that no symbol maps to
+    InvalidCode,
+
+    // the decoder finished but there were still bytes left over
+    TrailingBytes(usize),
+
+    // decoding would append more elements than the caller allowed
+    OutputTooLarge { requested: u64, limit: u64 },
+
+    // a string spec of an algorithm tree can't be parsed, `position` is the byte offset of the problem
+    InvalidSpec { position: usize, reason: &'static str },
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompressionError::Truncated => write!(f, "compressed buffer is truncated"),
+            CompressionError::UnknownMode(mode) => write!(f, "unknown count mode byte {mode}"),
+            CompressionError::UnknownAlgorithm(index) => write!(f, "unknown hybrid algorithm index {index}"),
+            CompressionError::ChunkOutOfRange { offset, count, available } => write!(f, "chunk at offset {offset} with {count} bytes is out of range ({available} bytes available)"),
+            CompressionError::UnknownEntry(index) => write!(f, "unknown lookup dictionary entry {index}"),
+            CompressionError::MismatchedRuns { counts, values } => write!(f, "decoded {counts} run counts but {values} run values"),
+            CompressionError::InvalidHeader(reason) => write!(f, "invalid header: {reason}"),
+            CompressionError::UnsupportedAlgorithm(id) => write!(f, "unsupported algorithm id {id}"),
+            CompressionError::LengthMismatch { expected, actual } => write!(f, "expected {expected} elements but decoded {actual}"),
+            CompressionError::RangeOutOfBounds { start, end, len } => write!(f, "range {start}..{end} is out of bounds for {len} elements"),
+            CompressionError::MatchOutOfRange { offset, available } => write!(f, "match offset {offset} is out of range ({available} elements decoded)"),
+            CompressionError::InvalidCode => write!(f, "invalid entropy code"),
+            CompressionError::TrailingBytes(count) => write!(f, "{count} trailing bytes after compressed data"),
+            CompressionError::OutputTooLarge { requested, limit } => write!(f, "decoding would produce {requested} elements, more than the limit of {limit}"),
+            CompressionError::InvalidSpec { position, reason } => write!(f, "invalid algorithm spec at byte {position}: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for CompressionError {}
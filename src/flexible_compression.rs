@@ -1,57 +1,312 @@
-use std::{io::{BufReader, Read}, marker::PhantomData};
-use rayon::{iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator}, slice::ParallelSlice, *};
-use bytemuck::{Pod, Zeroable};
+use std::marker::PhantomData;
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::error::CompressionError;
+use crate::descriptor::{AlgorithmId, Descriptor};
+use crate::algorithms::{Integer, encode_residuals, decode_residuals, shuffle_bytes, unshuffle_bytes, shuffle_bits, unshuffle_bits};
 
+// a reversible stage of a pipeline that may change the element type (T -> u8, T -> T::Unsigned, ...)
+pub trait Transform {
+    type Input;
+    type Output;
+    fn new() -> Self where Self: Sized;
+    fn forward(&self, input: &[Self::Input], output: &mut Vec<Self::Output>);
+    // fails instead of producing more than `limit` inputs
+    fn try_backward(&self, output: &[Self::Output], input: &mut Vec<Self::Input>, limit: usize) -> Result<(), CompressionError>;
+    // the most outputs `limit` inputs can turn into
+    fn output_limit(&self, limit: usize) -> usize;
+    fn descriptor(&self) -> Descriptor;
+}
+
+// starting point of every pipeline, hands the elements over as is
+pub struct Identity<T> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Copy> Transform for Identity<T> {
+    type Input = T;
+    type Output = T;
+
+    fn new() -> Self {
+        Self { _phantom: Default::default() }
+    }
+
+    fn forward(&self, input: &[T], output: &mut Vec<T>) {
+        output.extend_from_slice(input);
+    }
+
+    fn try_backward(&self, output: &[T], input: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        check_limit(output.len() as u64, limit)?;
+        input.extend_from_slice(output);
+        Ok(())
+    }
+
+    fn output_limit(&self, limit: usize) -> usize {
+        limit
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::Identity)
+    }
+}
+
+// runs `A` then `B` going forward, and `B` then `A` going backward
+pub struct Chain<A: Transform, B: Transform<Input = A::Output>> {
+    first: A,
+    second: B,
+}
+
+impl<A: Transform, B: Transform<Input = A::Output>> Transform for Chain<A, B> {
+    type Input = A::Input;
+    type Output = B::Output;
+
+    fn new() -> Self {
+        Self { first: A::new(), second: B::new() }
+    }
+
+    fn forward(&self, input: &[A::Input], output: &mut Vec<B::Output>) {
+        let mut intermediate = Vec::<A::Output>::with_capacity(input.len());
+        self.first.forward(input, &mut intermediate);
+        self.second.forward(&intermediate, output);
+    }
+
+    fn try_backward(&self, output: &[B::Output], input: &mut Vec<A::Input>, limit: usize) -> Result<(), CompressionError> {
+        let mut intermediate = Vec::<A::Output>::with_capacity(output.len());
+        self.second.try_backward(output, &mut intermediate, self.first.output_limit(limit))?;
+        self.first.try_backward(&intermediate, input, limit)
+    }
+
+    fn output_limit(&self, limit: usize) -> usize {
+        self.second.output_limit(self.first.output_limit(limit))
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::Chain, vec![self.first.descriptor(), self.second.descriptor()])
+    }
+}
+
+// reinterprets every element as its raw bytes
+pub struct Bytes<T> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Pod> Transform for Bytes<T> {
+    type Input = T;
+    type Output = u8;
+
+    fn new() -> Self {
+        Self { _phantom: Default::default() }
+    }
+
+    fn forward(&self, input: &[T], output: &mut Vec<u8>) {
+        output.extend_from_slice(bytemuck::cast_slice(input));
+    }
+
+    fn try_backward(&self, output: &[u8], input: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        NaiveCompressor::<T>::new().try_decompress_bounded(output, input, limit)
+    }
+
+    fn output_limit(&self, limit: usize) -> usize {
+        limit.saturating_mul(size_of::<T>())
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::Bytes)
+    }
+}
 
-/*
-struct Schema {
-    test: Vec<Box<dyn Compressor>>,
+// the transform half of `Shuffle`, outputs byte 0 of every element, then byte 1, ...
+pub struct ShuffledBytes<T> {
+    _phantom: PhantomData<T>,
 }
 
-impl Compressor for Schema {
-    fn new() -> Self where Self: Sized {
-        Schema {
-            test: Vec::default(),
-        }
+impl<T: Pod> Transform for ShuffledBytes<T> {
+    type Input = T;
+    type Output = u8;
+
+    fn new() -> Self {
+        Self { _phantom: Default::default() }
     }
 
-    fn compress(&self, uncompressed: &[u8], compressed: &mut Vec<u8>) {
-        let mut previous_input = Vec::from(uncompressed);
-        let mut previous_output = Vec::<u8>::new();
-        
-        for compressor in self.test.iter() {
-            previous_output.clear();
-            compressor.compress(&previous_input, &mut previous_output);
-            
-            std::mem::swap(&mut previous_input, &mut previous_output);
-        }
+    fn forward(&self, input: &[T], output: &mut Vec<u8>) {
+        shuffle_bytes(input, output);
+    }
 
-        compressed.extend_from_slice(&previous_input);
+    fn try_backward(&self, output: &[u8], input: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        check_limit((output.len() / size_of::<T>()) as u64, limit)?;
+        unshuffle_bytes(output, input)
     }
 
-    fn decompress(&self, compressed: &[u8], buffuncompresseder: &mut Vec<u8>) {
-        
+    // shuffling only moves bytes (or bits) around
+    fn output_limit(&self, limit: usize) -> usize {
+        limit.saturating_mul(size_of::<T>())
     }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::ShuffledBytes)
+    }
+}
+
+// the transform half of `BitShuffle`, outputs bit 0 of every element, then bit 1, ...
+pub struct ShuffledBits<T> {
+    _phantom: PhantomData<T>,
 }
 
-impl Schema {
-    fn new() -> Schema {
-        Schema { test:  Vec::new() }
+impl<T: Pod> Transform for ShuffledBits<T> {
+    type Input = T;
+    type Output = u8;
+
+    fn new() -> Self {
+        Self { _phantom: Default::default() }
+    }
+
+    fn forward(&self, input: &[T], output: &mut Vec<u8>) {
+        shuffle_bits(input, output);
+    }
+
+    fn try_backward(&self, output: &[u8], input: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        check_limit((output.len() / size_of::<T>()) as u64, limit)?;
+        unshuffle_bits(output, input)
+    }
+
+    // shuffling only moves bytes (or bits) around
+    fn output_limit(&self, limit: usize) -> usize {
+        limit.saturating_mul(size_of::<T>())
     }
 
-    fn add<T: Compressor + 'static>(&mut self) {
-        self.test.push(Box::new(T::new()));
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::ShuffledBits)
     }
-    
-    fn add_schema(&mut self, schema: Schema) {
-        self.test.push(Box::new(schema));
+}
+
+// the transform half of `Delta`, outputs zigzagged residuals
+pub struct Differences<T> {
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Integer> Transform for Differences<T> {
+    type Input = T;
+    type Output = T::Unsigned;
+
+    fn new() -> Self {
+        Self { _phantom: Default::default() }
+    }
+
+    fn forward(&self, input: &[T], output: &mut Vec<T::Unsigned>) {
+        encode_residuals(input, output);
+    }
+
+    fn try_backward(&self, output: &[T::Unsigned], input: &mut Vec<T>, limit: usize) -> Result<(), CompressionError> {
+        check_limit(output.len() as u64, limit)?;
+        decode_residuals(output, input);
+        Ok(())
+    }
+
+    fn output_limit(&self, limit: usize) -> usize {
+        limit
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::leaf(AlgorithmId::Differences)
+    }
+}
+
+// lets any compressor act as a pipeline stage, so that its bytes can be handed to the next stage
+pub struct Encoded<C: Compressor> {
+    compressor: C,
+}
+
+impl<C: Compressor> Encoded<C> {
+    pub fn new_with(compressor: C) -> Self {
+        Self { compressor }
+    }
+}
+
+impl<C: Compressor> Transform for Encoded<C> {
+    type Input = C::Input;
+    type Output = u8;
+
+    fn new() -> Self {
+        Self { compressor: C::new() }
+    }
+
+    fn forward(&self, input: &[C::Input], output: &mut Vec<u8>) {
+        self.compressor.compress(input, output);
+    }
+
+    fn try_backward(&self, output: &[u8], input: &mut Vec<C::Input>, limit: usize) -> Result<(), CompressionError> {
+        self.compressor.try_decompress_bounded(output, input, limit)
+    }
+
+    // there is no telling how long the compressed bytes of `limit` elements are, only how many a `Vec` can hold
+    fn output_limit(&self, _limit: usize) -> usize {
+        max_elements::<u8>()
+    }
+
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::Encoded, vec![self.compressor.descriptor()])
+    }
+}
+
+pub struct PipelineBuilder<S: Transform> {
+    stages: S,
+}
+
+// starts a pipeline that takes in elements of type `T`
+pub fn pipeline<T: Copy>() -> PipelineBuilder<Identity<T>> {
+    PipelineBuilder { stages: Identity::new() }
+}
+
+impl<S: Transform> PipelineBuilder<S> {
+    pub fn then<N: Transform<Input = S::Output>>(self) -> PipelineBuilder<Chain<S, N>> {
+        self.then_with(N::new())
+    }
+
+    pub fn then_with<N: Transform<Input = S::Output>>(self, stage: N) -> PipelineBuilder<Chain<S, N>> {
+        PipelineBuilder { stages: Chain { first: self.stages, second: stage } }
+    }
+
+    // same as `then`, but for stages that are compressors themselves
+    pub fn then_compress<C: Compressor<Input = S::Output>>(self) -> PipelineBuilder<Chain<S, Encoded<C>>> {
+        self.then_with(Encoded::new())
+    }
+
+    pub fn finish<C: Compressor<Input = S::Output>>(self) -> Pipeline<S, C> {
+        self.finish_with(C::new())
+    }
+
+    pub fn finish_with<C: Compressor<Input = S::Output>>(self, compressor: C) -> Pipeline<S, C> {
+        Pipeline { stages: self.stages, compressor }
+    }
+}
+
+// compresses by running every stage in order and then the final compressor
+// decompression runs them back in reverse
+pub struct Pipeline<S: Transform, C: Compressor<Input = S::Output>> {
+    stages: S,
+    compressor: C,
+}
+
+impl<S: Transform, C: Compressor<Input = S::Output>> Compressor for Pipeline<S, C> {
+    type Input = S::Input;
+
+    fn new() -> Self {
+        Self { stages: S::new(), compressor: C::new() }
+    }
+
+    fn compress(&self, uncompressed: &[S::Input], compressed: &mut Vec<u8>) {
+        let mut intermediate = Vec::<S::Output>::with_capacity(uncompressed.len());
+        self.stages.forward(uncompressed, &mut intermediate);
+        self.compressor.compress(&intermediate, compressed);
+    }
+
+    fn try_decompress_bounded(&self, compressed: &[u8], uncompressed: &mut Vec<S::Input>, limit: usize) -> Result<(), CompressionError> {
+        let mut intermediate = Vec::<S::Output>::new();
+        self.compressor.try_decompress_bounded(compressed, &mut intermediate, self.stages.output_limit(limit))?;
+        self.stages.try_backward(&intermediate, uncompressed, limit)
     }
 
-    fn compress(&self, input: &[u8]) -> Vec<u8> {
-        let mut output = Vec::<u8>::new();
-        let compressor: &dyn Compressor = self;
-        compressor.compress(&input, &mut output);
-        output
+    fn descriptor(&self) -> Descriptor {
+        Descriptor::node(AlgorithmId::Pipeline, vec![self.stages.descriptor(), self.compressor.descriptor()])
     }
 }
-*/
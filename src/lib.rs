@@ -1,9 +1,19 @@
 mod flexible_compression;
 mod compressor;
 mod algorithms;
+mod error;
+mod descriptor;
+mod registry;
+mod container;
+mod stream;
+#[cfg(test)]
 mod tests;
 
 pub use compressor::*;
 pub use algorithms::*;
-
-
+pub use error::*;
+pub use descriptor::*;
+pub use registry::*;
+pub use container::*;
+pub use stream::*;
+pub use flexible_compression::*;
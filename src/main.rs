@@ -1,27 +1,223 @@
-mod flexible_compression;
-mod compressor;
-mod algorithms;
-mod tests;
-
-use std::{any::TypeId, hash::Hash};
+use std::{process::ExitCode, time::{Duration, Instant}};
 
 use bytemuck::{Pod, Zeroable};
-pub use algorithms::*;
+use compression_experiments::*;
+
+const USAGE: &str = "usage:
+  compression-experiments compress <input> <output> --type <type> [--algorithm <algorithm>]
+  compression-experiments decompress <input> <output>
+  compression-experiments analyze <input> --type <type>
+  compression-experiments synthetic
+
+types: u8, u16, u32, u64, i32, f32
+algorithms: rle, vrle, lookup, lz, huffman, arithmetic, arithmetic1, bitpack, pfor, gorilla, delta, dod, shuffle-lz, bitshuffle-lz, hybrid, segmented, optionally prefixed with 'parchunked-' (default: parchunked-hybrid)
+  or any spec like 'parchunked(hybrid(rle,vrle(lookup,naive),delta(bitpack)))'";
+
+const ALGORITHMS: [&str; 31] = [
+    "rle", "vrle", "lookup", "lz", "huffman", "arithmetic", "arithmetic1", "bitpack", "pfor", "gorilla", "delta", "dod",
+    "shuffle-lz", "bitshuffle-lz", "hybrid", "segmented",
+    "parchunked-rle", "parchunked-vrle", "parchunked-lookup", "parchunked-lz", "parchunked-huffman",
+    "parchunked-arithmetic", "parchunked-arithmetic1", "parchunked-bitpack", "parchunked-pfor", "parchunked-gorilla",
+    "parchunked-delta", "parchunked-dod", "parchunked-shuffle-lz", "parchunked-bitshuffle-lz", "parchunked-hybrid",
+];
+
+fn parse_kind(name: &str) -> Result<ElementKind, String> {
+    match name {
+        "u8" => Ok(ElementKind::U8),
+        "u16" => Ok(ElementKind::U16),
+        "u32" => Ok(ElementKind::U32),
+        "u64" => Ok(ElementKind::U64),
+        "i32" => Ok(ElementKind::I32),
+        "f32" => Ok(ElementKind::F32),
+        _ => Err(format!("unknown element type '{name}'")),
+    }
+}
+
+// the CLI's presets on top of the algorithm names, every one of them can also be wrapped as 'parchunked-<preset>'
+const PRESETS: [(&str, &str); 5] = [
+    ("delta", "delta(vrle)"),
+    ("shuffle-lz", "shuffle(lz)"),
+    ("bitshuffle-lz", "bitshuffle(lz)"),
+    ("hybrid", "hybrid(vrle,rle,lookup,lz,huffman,vrle(naive,huffman),rle(lookup,naive),vrle(lookup,naive))"),
+    ("segmented", "segmented(vrle,delta,lookup,bitpack,lz,naive)"),
+];
+
+fn registry() -> Result<Registry, CompressionError> {
+    let mut registry = Registry::new();
+    for (name, spec) in PRESETS {
+        registry = registry.with_alias(name, spec)?;
+    }
+
+    for name in ALGORITHMS.iter().filter(|name| !name.starts_with("parchunked-")) {
+        registry = registry.with_alias(&format!("parchunked-{name}"), &format!("parchunked({name})"))?;
+    }
+
+    Ok(registry)
+}
+
+// algorithm stacks are described as descriptor trees, so that they can be built for any element width
+fn parse_algorithm(spec: &str) -> Result<Descriptor, String> {
+    registry().and_then(|registry| registry.parse(spec)).map_err(|err| err.to_string())
+}
+
+// looks up the value following `flag`
+fn flag<'a>(args: &'a [String], flag: &str) -> Result<Option<&'a str>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(index) => args.get(index + 1).map(|value| Some(value.as_str())).ok_or(format!("missing value for {flag}")),
+        None => Ok(None),
+    }
+}
+
+fn read_elements<T: Pod>(bytes: &[u8]) -> Result<Vec<T>, String> {
+    if !bytes.len().is_multiple_of(size_of::<T>()) {
+        return Err(format!("input size {} is not a multiple of the element size {}", bytes.len(), size_of::<T>()));
+    }
+
+    Ok(bytes.chunks_exact(size_of::<T>()).map(bytemuck::pod_read_unaligned).collect())
+}
+
+fn compress_file<T: Integer>(bytes: &[u8], kind: ElementKind, descriptor: &Descriptor) -> Result<Vec<u8>, String> {
+    let elements = read_elements::<T>(bytes)?;
+    let compressor = build::<T>(descriptor).map_err(|err| err.to_string())?;
+    let mut framed = Vec::<u8>::new();
+    frame(&compressor, kind, &elements, &mut framed);
+    Ok(framed)
+}
+
+fn run_compress(args: &[String]) -> Result<(), String> {
+    let [input, output, ..] = args else {
+        return Err("compress needs an input and an output path".to_string());
+    };
+
+    let kind = parse_kind(flag(args, "--type")?.ok_or("missing --type")?)?;
+    let descriptor = parse_algorithm(flag(args, "--algorithm")?.unwrap_or("parchunked-hybrid"))?;
+    let bytes = std::fs::read(input).map_err(|err| format!("failed to read '{input}': {err}"))?;
+
+    let framed = match kind.size() {
+        1 => compress_file::<u8>(&bytes, kind, &descriptor)?,
+        2 => compress_file::<u16>(&bytes, kind, &descriptor)?,
+        4 => compress_file::<u32>(&bytes, kind, &descriptor)?,
+        _ => compress_file::<u64>(&bytes, kind, &descriptor)?,
+    };
+
+    std::fs::write(output, &framed).map_err(|err| format!("failed to write '{output}': {err}"))?;
+    println!("{} -> {} bytes ({:.2}%)", bytes.len(), framed.len(), framed.len() as f64 / bytes.len().max(1) as f64 * 100.0);
+    Ok(())
+}
+
+fn run_decompress(args: &[String]) -> Result<(), String> {
+    let [input, output, ..] = args else {
+        return Err("decompress needs an input and an output path".to_string());
+    };
 
-use crate::compressor::Compressor;
+    let framed = std::fs::read(input).map_err(|err| format!("failed to read '{input}': {err}"))?;
+    let (header, elements) = decode_any(&framed).map_err(|err| err.to_string())?;
+    std::fs::write(output, elements.as_bytes()).map_err(|err| format!("failed to write '{output}': {err}"))?;
+    let spec = registry().map_err(|err| err.to_string())?.print(&header.descriptor);
+    println!("decompressed {} elements of type {:?} compressed with {spec}", header.count, header.kind);
+    Ok(())
+}
+
+struct Measurement {
+    size: usize,
+    encode: Duration,
+    decode: Duration,
+}
+
+fn measure<T: Integer>(elements: &[T], descriptor: &Descriptor) -> Result<Measurement, String> {
+    let compressor = build::<T>(descriptor).map_err(|err| err.to_string())?;
+
+    let start = Instant::now();
+    let mut compressed = Vec::<u8>::new();
+    compressor.compress(elements, &mut compressed);
+    let encode = start.elapsed();
+
+    let start = Instant::now();
+    let mut decompressed = Vec::<T>::with_capacity(elements.len());
+    compressor.try_decompress_bounded(&compressed, &mut decompressed, elements.len()).map_err(|err| err.to_string())?;
+    let decode = start.elapsed();
+
+    if decompressed != elements {
+        return Err("round trip produced different elements".to_string());
+    }
+
+    Ok(Measurement { size: compressed.len(), encode, decode })
+}
+
+fn analyze_file<T: Integer>(bytes: &[u8]) -> Result<(), String> {
+    let elements = read_elements::<T>(bytes)?;
+    println!("{:<20} {:>12} {:>9} {:>12} {:>12}", "algorithm", "size", "ratio", "encode", "decode");
+
+    for name in ALGORITHMS {
+        // not every algorithm works on every element width (Gorilla needs 4 or 8 bytes)
+        let descriptor = parse_algorithm(name)?;
+        if build::<T>(&descriptor).is_err() {
+            println!("{:<20} {:>12}", name, "unsupported");
+            continue;
+        }
+
+        let measurement = measure(&elements, &descriptor)?;
+        println!("{:<20} {:>12} {:>8.2}% {:>12.2?} {:>12.2?}",
+            name,
+            measurement.size,
+            measurement.size as f64 / bytes.len().max(1) as f64 * 100.0,
+            measurement.encode,
+            measurement.decode
+        );
+    }
+
+    // the same candidates as "hybrid", but picked from samples instead of running every one on all of the data
+    let mut hybrid = Hybrid::<T>::new().with_selection(Selection::sampled());
+    for child in parse_algorithm("hybrid")?.children.iter() {
+        hybrid = hybrid.with(build::<T>(child).map_err(|err| err.to_string())?);
+    }
+
+    let start = Instant::now();
+    let mut compressed = Vec::<u8>::new();
+    let report = hybrid.compress_with_report(&elements, &mut compressed);
+    let encode = start.elapsed();
+    println!("{:<20} {:>12} {:>8.2}% {:>12.2?}   picked #{} with {:.0}% confidence{}",
+        "hybrid-sampled",
+        compressed.len(),
+        compressed.len() as f64 / bytes.len().max(1) as f64 * 100.0,
+        encode,
+        report.index,
+        report.confidence * 100.0,
+        if report.exhaustive { ", fell back to trying all" } else { "" }
+    );
+
+    Ok(())
+}
+
+fn run_analyze(args: &[String]) -> Result<(), String> {
+    let [input, ..] = args else {
+        return Err("analyze needs an input path".to_string());
+    };
+
+    let kind = parse_kind(flag(args, "--type")?.ok_or("missing --type")?)?;
+    let bytes = std::fs::read(input).map_err(|err| format!("failed to read '{input}': {err}"))?;
+    println!("Analyzing '{input}' as {kind:?} ({} bytes)", bytes.len());
+
+    match kind.size() {
+        1 => analyze_file::<u8>(&bytes),
+        2 => analyze_file::<u16>(&bytes),
+        4 => analyze_file::<u32>(&bytes),
+        _ => analyze_file::<u64>(&bytes),
+    }
+}
 
 fn compress_into_void<C: Compressor<Input = T>, T: Pod + Zeroable + Sync + Send>(data: &[T]) -> u64 {
     let compressor = C::new();
     let mut output = Vec::<u8>::with_capacity(10000000);
-    compressor.compress(&data, &mut output);
-    assert!(output.len() > 0);
+    compressor.compress(data, &mut output);
+    assert!(!output.is_empty());
     output.len() as u64
 }
 
 fn compress_into_void_with<C: Compressor<Input = T>, T: Pod + Zeroable + Sync + Send>(compressor: C, data: &[T]) -> u64 {
     let mut output = Vec::<u8>::with_capacity(10000000);
-    compressor.compress(&data, &mut output);
-    assert!(output.len() > 0);
+    compressor.compress(data, &mut output);
+    assert!(!output.is_empty());
     output.len() as u64
 }
 
@@ -36,36 +232,77 @@ fn pseudo_random(seed: u32) -> u32 {
     value
 }
 
-fn main() {
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let result = match args.first().map(String::as_str) {
+        Some("compress") => run_compress(&args[1..]),
+        Some("decompress") => run_decompress(&args[1..]),
+        Some("analyze") => run_analyze(&args[1..]),
+        Some("synthetic") => {
+            run_synthetic();
+            Ok(())
+        },
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn run_synthetic() {
     static JUMP: usize = 10_000;
 
     for size in [JUMP, 2 * JUMP, 4 * JUMP, 8 * JUMP, 16 * JUMP, 32 * JUMP, 64 * JUMP].iter() {
         test_for_data_set("sequential", (0..*size).map(|i| i as u32));
-        test_for_data_set("constant", (0..*size).map(|_| 420_6767_420 as u64));
+        test_for_data_set("constant", (0..*size).map(|_| 4_206_767_420u64));
         test_for_data_set("modulo", (0..*size).map(|i| (i % 52) as u64));
-        test_for_data_set("pseudo-random", (0..*size).map(|i| pseudo_random(i as u32) as u32));
-        test_for_data_set("sine", (0..*size).map(|i| ((i as f32 * std::f32::consts::PI / 2.0).sin() as f32 * 20.0) as i32));
+        test_for_data_set("pseudo-random", (0..*size).map(|i| pseudo_random(i as u32)));
+        test_for_data_set("constant + pseudo-random", (0..*size).map(|i| if i < size / 2 { 4_206_767 } else { pseudo_random(i as u32) }));
+        test_for_data_set("sine", (0..*size).map(|i| ((i as f32 * std::f32::consts::PI / 2.0).sin() * 20.0) as i32));
+        test_for_data_set("smooth sine (f32 bits)", (0..*size).map(|i| ((i as f32 * 0.01).sin() * 20.0).to_bits()));
     }
 }
 
-fn test_for_data_set<T: Pod + Zeroable + Send + Sync + PartialEq + Eq + Hash>(name: &str, data: impl Iterator<Item = T>) {
+fn test_for_data_set<T: Integer>(name: &str, data: impl Iterator<Item = T>) {
     let data = data.collect::<Vec<T>>();
     let rle_size = compress_into_void::<RLE<T>, T>(&data);
     let vrle_size = compress_into_void::<VRLE<T>, T>(&data);
     let par_rle_size = compress_into_void::<ParChunked<RLE<T>>, T>(&data);
     let par_vrle_size = compress_into_void::<ParChunked<VRLE<T>>, T>(&data);
     let lookup_size = compress_into_void::<Lookup<T>, T>(&data);
+    let lz_size = compress_into_void::<LZ<T>, T>(&data);
+    let huffman_size = compress_into_void::<Huffman<T>, T>(&data);
+    let arithmetic_size = compress_into_void::<Arithmetic<T, Order1>, T>(&data);
+    let bitpack_size = compress_into_void::<BitPack<T>, T>(&data);
+    let pfor_size = compress_into_void::<PFor<T>, T>(&data);
+    let gorilla_size = build::<T>(&Descriptor::leaf(AlgorithmId::Gorilla)).ok().map(|gorilla| compress_into_void_with(gorilla, &data));
 
     let compressor = ParChunked::new_with(
         Hybrid::new()
             .add::<VRLE<T>>()
             .add::<RLE<T>>()
             .add::<Lookup<T>>()
+            .add::<LZ<T>>()
+            .add::<Huffman<T>>()
+            .add::<VRLE<T, NaiveCompressor<T>, Huffman>>()
             .add::<RLE<T, Lookup<T>>>()
             .add::<VRLE<T, Lookup<T>>>(),
     None);
     let wtf = compress_into_void_with(compressor, &data);
 
+    let segmented = Segmented::new()
+        .add::<VRLE<T>>()
+        .add::<Lookup<T>>()
+        .add::<BitPack<T>>()
+        .add::<NaiveCompressor<T>>();
+    let segmented_size = compress_into_void_with(segmented, &data);
+
     let original_size = (data.len() * std::mem::size_of::<T>()) as u64;
 
     let type_name = std::any::type_name::<T>();
@@ -76,6 +313,15 @@ fn test_for_data_set<T: Pod + Zeroable + Send + Sync + PartialEq + Eq + Hash>(na
     println!("  ParChunked<RLE>: {:.2}%", (par_rle_size as f64 / original_size as f64) * 100.0);
     println!("  ParChunked<VRLE>: {:.2}%", (par_vrle_size as f64 / original_size as f64) * 100.0);
     println!("  Lookup: {:.2}%", (lookup_size as f64 / original_size as f64) * 100.0);
+    println!("  LZ: {:.2}%", (lz_size as f64 / original_size as f64) * 100.0);
+    println!("  Huffman: {:.2}%", (huffman_size as f64 / original_size as f64) * 100.0);
+    println!("  Arithmetic<Order1>: {:.2}%", (arithmetic_size as f64 / original_size as f64) * 100.0);
+    println!("  BitPack: {:.2}%", (bitpack_size as f64 / original_size as f64) * 100.0);
+    println!("  PFor: {:.2}%", (pfor_size as f64 / original_size as f64) * 100.0);
+    if let Some(gorilla_size) = gorilla_size {
+        println!("  Gorilla: {:.2}%", (gorilla_size as f64 / original_size as f64) * 100.0);
+    }
     println!("  WTF: {:.2}%", (wtf as f64 / original_size as f64) * 100.0);
+    println!("  Segmented: {:.2}%", (segmented_size as f64 / original_size as f64) * 100.0);
     println!();
 }
\ No newline at end of file
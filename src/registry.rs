@@ -0,0 +1,237 @@
+use std::{fmt, str::FromStr};
+use crate::error::CompressionError;
+use crate::algorithms::Integer;
+use crate::descriptor::{AlgorithmId, Descriptor, DynCompressor, MAX_DEPTH, build};
+
+// stable name of every algorithm in string specs, in id order, never rename these
+const NAMES: [(AlgorithmId, &str); 28] = [
+    (AlgorithmId::Naive, "naive"),
+    (AlgorithmId::RLE, "rle"),
+    (AlgorithmId::VRLE, "vrle"),
+    (AlgorithmId::ParChunked, "parchunked"),
+    (AlgorithmId::Hybrid, "hybrid"),
+    (AlgorithmId::Lookup, "lookup"),
+    (AlgorithmId::Delta, "delta"),
+    (AlgorithmId::Pipeline, "pipeline"),
+    (AlgorithmId::Identity, "identity"),
+    (AlgorithmId::Chain, "chain"),
+    (AlgorithmId::Bytes, "bytes"),
+    (AlgorithmId::Differences, "differences"),
+    (AlgorithmId::Encoded, "encoded"),
+    (AlgorithmId::LZ, "lz"),
+    (AlgorithmId::Huffman, "huffman"),
+    (AlgorithmId::ArithmeticOrder0, "arithmetic"),
+    (AlgorithmId::ArithmeticOrder1, "arithmetic1"),
+    (AlgorithmId::BitPack, "bitpack"),
+    (AlgorithmId::PFor, "pfor"),
+    (AlgorithmId::Gorilla, "gorilla"),
+    (AlgorithmId::DeltaOfDelta, "dod"),
+    (AlgorithmId::Buckets, "buckets"),
+    (AlgorithmId::Shuffle, "shuffle"),
+    (AlgorithmId::BitShuffle, "bitshuffle"),
+    (AlgorithmId::ShuffledBytes, "shuffledbytes"),
+    (AlgorithmId::ShuffledBits, "shuffledbits"),
+    (AlgorithmId::Columnar, "columnar"),
+    (AlgorithmId::Segmented, "segmented"),
+];
+
+impl AlgorithmId {
+    pub fn name(self) -> &'static str {
+        NAMES[self as usize].1
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMES.iter().find(|(_, known)| *known == name).map(|(id, _)| *id)
+    }
+
+    // what a bare name stands for, the same inner algorithms that `new()` uses
+    pub fn default_children(self) -> Vec<Descriptor> {
+        let naive = || Descriptor::leaf(AlgorithmId::Naive);
+
+        match self {
+            Self::RLE | Self::VRLE => vec![naive(), naive()],
+            Self::Delta | Self::Shuffle | Self::BitShuffle => vec![naive()],
+            Self::DeltaOfDelta => vec![Descriptor::leaf(Self::Buckets)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_'
+}
+
+struct Parser<'a> {
+    spec: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, reason: &'static str) -> CompressionError {
+        CompressionError::InvalidSpec { position: self.position, reason }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.spec.as_bytes().get(self.position).is_some_and(u8::is_ascii_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.spec.as_bytes().get(self.position) == Some(&byte);
+        self.position += found as usize;
+        found
+    }
+
+    fn name(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.spec.as_bytes().get(self.position).copied().is_some_and(is_name_byte) {
+            self.position += 1;
+        }
+
+        &self.spec[start..self.position]
+    }
+}
+
+// turns string specs like "parchunked(hybrid(rle,vrle,lookup))" into descriptors and back
+//   spec := name | name '(' [spec {',' spec}] ')'
+// a bare algorithm name gets its default inner algorithms, so "rle" is "rle(naive,naive)"
+// aliases name whole trees, a bare name is looked up in the aliases first but a name with parentheses is always the algorithm
+#[derive(Debug, Clone, Default)]
+pub struct Registry {
+    aliases: Vec<(String, Descriptor)>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `spec` may use the aliases added before this one
+    pub fn with_alias(mut self, name: &str, spec: &str) -> Result<Self, CompressionError> {
+        if name.is_empty() || !name.bytes().all(is_name_byte) {
+            return Err(CompressionError::InvalidSpec { position: 0, reason: "alias names are made of letters, digits, '-' and '_'" });
+        }
+
+        let descriptor = self.parse(spec)?;
+        self.aliases.retain(|(known, _)| known != name);
+        self.aliases.push((name.to_string(), descriptor));
+        Ok(self)
+    }
+
+    pub fn alias(&self, name: &str) -> Option<&Descriptor> {
+        self.aliases.iter().find(|(known, _)| known == name).map(|(_, descriptor)| descriptor)
+    }
+
+    pub fn aliases(&self) -> impl Iterator<Item = &str> {
+        self.aliases.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn parse(&self, spec: &str) -> Result<Descriptor, CompressionError> {
+        let mut parser = Parser { spec, position: 0 };
+        let descriptor = self.parse_node(&mut parser, 0)?;
+
+        parser.skip_whitespace();
+        if parser.position != spec.len() {
+            return Err(parser.error("unexpected characters after the spec"));
+        }
+
+        Ok(descriptor)
+    }
+
+    fn parse_node(&self, parser: &mut Parser, depth: usize) -> Result<Descriptor, CompressionError> {
+        if depth > MAX_DEPTH {
+            return Err(parser.error("algorithm tree is too deep"));
+        }
+
+        parser.skip_whitespace();
+        let start = parser.position;
+        let name = parser.name();
+        if name.is_empty() {
+            return Err(parser.error("expected an algorithm name"));
+        }
+
+        let unknown = CompressionError::InvalidSpec { position: start, reason: "unknown algorithm name" };
+        if !parser.eat(b'(') {
+            if let Some(descriptor) = self.alias(name) {
+                return Ok(descriptor.clone());
+            }
+
+            let algorithm = AlgorithmId::from_name(name).ok_or(unknown)?;
+            return Ok(Descriptor::node(algorithm, algorithm.default_children()));
+        }
+
+        let algorithm = AlgorithmId::from_name(name).ok_or(unknown)?;
+        let mut children = Vec::new();
+        if !parser.eat(b')') {
+            loop {
+                children.push(self.parse_node(parser, depth + 1)?);
+
+                if parser.eat(b')') {
+                    break;
+                }
+
+                if !parser.eat(b',') {
+                    return Err(parser.error("expected ',' or ')'"));
+                }
+            }
+        }
+
+        // the child count is stored in a byte in framed headers
+        if children.len() > u8::MAX as usize {
+            return Err(CompressionError::InvalidSpec { position: start, reason: "too many inner algorithms" });
+        }
+
+        Ok(Descriptor::node(algorithm, children))
+    }
+
+    // the shortest spec that parses back to `descriptor`
+    pub fn print(&self, descriptor: &Descriptor) -> String {
+        let mut spec = String::new();
+        self.print_node(descriptor, &mut spec);
+        spec
+    }
+
+    fn print_node(&self, descriptor: &Descriptor, spec: &mut String) {
+        if let Some((name, _)) = self.aliases.iter().find(|(_, alias)| alias == descriptor) {
+            spec.push_str(name);
+            return;
+        }
+
+        let name = descriptor.algorithm.name();
+        spec.push_str(name);
+        if self.alias(name).is_none() && descriptor.children == descriptor.algorithm.default_children() {
+            return;
+        }
+
+        spec.push('(');
+        for (i, child) in descriptor.children.iter().enumerate() {
+            if i > 0 {
+                spec.push(',');
+            }
+
+            self.print_node(child, spec);
+        }
+        spec.push(')');
+    }
+
+    pub fn build<T: Integer>(&self, spec: &str) -> Result<DynCompressor<T>, CompressionError> {
+        build::<T>(&self.parse(spec)?)
+    }
+}
+
+impl fmt::Display for Descriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Registry::new().print(self))
+    }
+}
+
+impl FromStr for Descriptor {
+    type Err = CompressionError;
+
+    fn from_str(spec: &str) -> Result<Self, CompressionError> {
+        Registry::new().parse(spec)
+    }
+}
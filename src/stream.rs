@@ -0,0 +1,192 @@
+use std::io::{self, Read, Write};
+use bytemuck::Pod;
+use crate::compressor::*;
+use crate::error::CompressionError;
+
+// number of elements buffered before a block gets compressed and written out
+pub const DEFAULT_BLOCK_SIZE: usize = 1 << 16;
+
+// every block is independently decodable:
+// element count (u64 LE), compressed length (u64 LE), compressed bytes
+const BLOCK_HEADER_SIZE: usize = 16;
+
+fn invalid_data(err: CompressionError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+// buffers at most `block_size` elements, then compresses them as a single block
+// call `finish` to write out the last (partial) block
+pub struct CompressWriter<W: Write, C: Compressor> {
+    writer: W,
+    compressor: C,
+    block_size: usize,
+    elements: Vec<C::Input>,
+    pending: Vec<u8>,
+    scratch: Vec<u8>,
+}
+
+impl<W: Write, C: Compressor> CompressWriter<W, C> where C::Input: Pod {
+    pub fn new(writer: W, compressor: C) -> Self {
+        Self {
+            writer,
+            compressor,
+            block_size: DEFAULT_BLOCK_SIZE,
+            elements: Vec::new(),
+            pending: Vec::new(),
+            scratch: Vec::new(),
+        }
+    }
+
+    pub fn with_block_size(mut self, block_size: usize) -> Self {
+        assert!(block_size > 0);
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn write_elements(&mut self, mut elements: &[C::Input]) -> io::Result<()> {
+        while !elements.is_empty() {
+            let taken = (self.block_size - self.elements.len()).min(elements.len());
+            self.elements.extend_from_slice(&elements[..taken]);
+            elements = &elements[taken..];
+
+            if self.elements.len() == self.block_size {
+                self.write_block()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.elements.is_empty() {
+            return Ok(());
+        }
+
+        self.scratch.clear();
+        self.compressor.compress(&self.elements, &mut self.scratch);
+
+        let mut header = [0u8; BLOCK_HEADER_SIZE];
+        header[..8].copy_from_slice(&u64::to_le_bytes(self.elements.len() as u64));
+        header[8..].copy_from_slice(&u64::to_le_bytes(self.scratch.len() as u64));
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&self.scratch)?;
+
+        self.elements.clear();
+        Ok(())
+    }
+
+    // writes the last block and hands back the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.pending.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "stream ended in the middle of an element"));
+        }
+
+        self.write_block()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+// raw bytes are reinterpreted as elements, partial elements are kept until the rest of their bytes arrive
+impl<W: Write, C: Compressor> Write for CompressWriter<W, C> where C::Input: Pod {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+
+        let complete = self.pending.len() - self.pending.len() % size_of::<C::Input>();
+        let elements = self.pending[..complete]
+            .chunks_exact(size_of::<C::Input>())
+            .map(bytemuck::pod_read_unaligned::<C::Input>)
+            .collect::<Vec<_>>();
+
+        self.pending.drain(..complete);
+        self.write_elements(&elements)?;
+        Ok(buf.len())
+    }
+
+    // only flushes the inner writer, buffered elements stay until their block is full
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// decodes the blocks written by `CompressWriter` one at a time
+pub struct DecompressReader<R: Read, C: Compressor> {
+    reader: R,
+    compressor: C,
+    block: Vec<C::Input>,
+    position: usize,
+    scratch: Vec<u8>,
+}
+
+impl<R: Read, C: Compressor> DecompressReader<R, C> where C::Input: Pod {
+    pub fn new(reader: R, compressor: C) -> Self {
+        Self {
+            reader,
+            compressor,
+            block: Vec::new(),
+            position: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    // returns `None` once the stream cleanly ends on a block boundary
+    pub fn read_block(&mut self) -> io::Result<Option<Vec<C::Input>>> {
+        let mut header = [0u8; BLOCK_HEADER_SIZE];
+        let mut filled = 0;
+
+        while filled < header.len() {
+            match self.reader.read(&mut header[filled..]) {
+                Ok(0) if filled == 0 => return Ok(None),
+                Ok(0) => return Err(invalid_data(CompressionError::Truncated)),
+                Ok(read) => filled += read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let count = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let length = u64::from_le_bytes(header[8..].try_into().unwrap());
+
+        // grows as data actually arrives, so a corrupted length can't make us allocate it all up front
+        self.scratch.clear();
+        (&mut self.reader).take(length).read_to_end(&mut self.scratch)?;
+        if (self.scratch.len() as u64) < length {
+            return Err(invalid_data(CompressionError::Truncated));
+        }
+
+        let mut elements = Vec::<C::Input>::new();
+        let limit = usize::try_from(count).unwrap_or(usize::MAX);
+        self.compressor.try_decompress_bounded(&self.scratch, &mut elements, limit).map_err(invalid_data)?;
+
+        if elements.len() as u64 != count {
+            return Err(invalid_data(CompressionError::LengthMismatch { expected: count, actual: elements.len() as u64 }));
+        }
+
+        Ok(Some(elements))
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+// hands out the raw bytes of the decoded elements
+impl<R: Read, C: Compressor> Read for DecompressReader<R, C> where C::Input: Pod {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position == size_of_val(self.block.as_slice()) {
+            match self.read_block()? {
+                Some(block) => {
+                    self.block = block;
+                    self.position = 0;
+                },
+                None => return Ok(0),
+            }
+        }
+
+        let bytes = &bytemuck::cast_slice::<C::Input, u8>(&self.block)[self.position..];
+        let read = bytes.len().min(buf.len());
+        buf[..read].copy_from_slice(&bytes[..read]);
+        self.position += read;
+        Ok(read)
+    }
+}
@@ -1,5 +1,4 @@
 use crate::*;
-use crate::compressor::*;
 use bytemuck::{Pod, Zeroable};
 
 #[test]
@@ -201,4 +200,1463 @@ fn test_parchunked_rle_large_custom_struct() {
     let mut decompressed = Vec::new();
     par_rle.decompress(&compressed, &mut decompressed);
     assert_eq!(decompressed, input);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_rle_truncated_input() {
+    let rle = RLE::<u32>::new();
+    let input = vec![1u32, 1, 1, 2, 2, 3];
+    let mut compressed = Vec::new();
+    rle.compress(&input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    // the value stream comes last, cutting a byte off leaves a partial element at the end
+    let result = rle.try_decompress(&compressed[..compressed.len() - 1], &mut decompressed);
+    assert_eq!(result, Err(CompressionError::Truncated));
+}
+
+#[test]
+fn test_rle_hostile_run_counts() {
+    let rle = RLE::<u8>::new();
+    let mut decompressed = Vec::new();
+
+    // one run of u64::MAX elements from an 11 byte buffer, more than any `Vec` can hold
+    let blob = [&[0, 8][..], &u64::MAX.to_le_bytes(), &[5]].concat();
+    let result = rle.try_decompress(&blob, &mut decompressed);
+    assert_eq!(result, Err(CompressionError::OutputTooLarge { requested: u64::MAX, limit: max_elements::<u8>() as u64 }));
+
+    // two runs whose lengths only overflow when added up
+    let blob = [&[0, 16][..], &u64::MAX.to_le_bytes(), &2u64.to_le_bytes(), &[5, 6]].concat();
+    assert!(matches!(rle.try_decompress(&blob, &mut decompressed), Err(CompressionError::OutputTooLarge { .. })));
+
+    // 2^40 elements fit in a `Vec`, but not in what the caller allows
+    let blob = [&[0, 0, 9, 3][..], &(1u64 << 40).to_le_bytes(), &[5, 0, 0, 0]].concat();
+    let result = VRLE::<u32>::new().try_decompress_bounded(&blob, &mut Vec::new(), 1 << 20);
+    assert_eq!(result, Err(CompressionError::OutputTooLarge { requested: 1 << 40, limit: 1 << 20 }));
+    assert!(decompressed.is_empty());
+
+    // the limit is inclusive
+    let mut compressed = Vec::new();
+    rle.compress(&[5; 1000], &mut compressed);
+    assert!(rle.try_decompress_bounded(&compressed, &mut decompressed, 1000).is_ok());
+    assert_eq!(rle.try_decompress_bounded(&compressed, &mut Vec::new(), 999), Err(CompressionError::OutputTooLarge { requested: 1000, limit: 999 }));
+}
+
+#[test]
+fn test_long_constant_run() {
+    // far more output per compressed byte than any fixed ratio would have allowed, and zeroed memory is barely
+    // touched on the way in
+    let input = vec![0u64; 40_000_000];
+    assert!(round_trip(&RLE::<u64>::new(), &input) < 32);
+    assert!(round_trip(&VRLE::<u64>::new(), &input) < 32);
+    assert!(round_trip(&LZ::<u64>::new(), &input) < 32);
+}
+
+#[test]
+fn test_vrle_unknown_mode() {
+    let vrle = VRLE::<u8>::new();
+    let mut decompressed = Vec::new();
+    let result = vrle.try_decompress(&[0, 7, 1, 42], &mut decompressed);
+    assert_eq!(result, Err(CompressionError::UnknownMode(7)));
+
+    let result = vrle.try_decompress(&[9, 0, 0, 0], &mut decompressed);
+    assert_eq!(result, Err(CompressionError::InvalidHeader("unknown count layout")));
+}
+
+#[test]
+fn test_hybrid_unknown_algorithm() {
+    let hybrid = Hybrid::<u8>::new().add::<RLE<u8>>().add::<VRLE<u8>>();
+    let mut decompressed = Vec::new();
+    let result = hybrid.try_decompress(&[2, 0, 0], &mut decompressed);
+    assert_eq!(result, Err(CompressionError::UnknownAlgorithm(2)));
+    assert_eq!(hybrid.try_decompress(&[], &mut decompressed), Err(CompressionError::Truncated));
+}
+
+#[test]
+fn test_parchunked_corrupted_chunk_table() {
+    let par_rle = ParChunked { compressor: RLE::<u8>::new(), chunk_size: Some(4) };
+    let input = vec![1u8, 1, 2, 2, 3, 3, 4, 4];
+    let mut compressed = Vec::new();
+    par_rle.compress(&input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    let result = par_rle.try_decompress(&compressed[..compressed.len() - 3], &mut decompressed);
+    assert!(matches!(result, Err(CompressionError::ChunkOutOfRange { .. })));
+
+    compressed.push(0);
+    let result = par_rle.try_decompress(&compressed, &mut decompressed);
+    assert_eq!(result, Err(CompressionError::TrailingBytes(1)));
+}
+
+#[test]
+fn test_naive_trailing_bytes() {
+    let naive = NaiveCompressor::<u32>::new();
+    let mut decompressed = Vec::new();
+    let result = naive.try_decompress(&[1, 2, 3, 4, 5], &mut decompressed);
+    assert_eq!(result, Err(CompressionError::TrailingBytes(1)));
+}
+
+#[test]
+fn test_delta_round_trip_wrapping() {
+    let delta = Delta::<u8>::new();
+    let input = vec![250u8, 255, 3, 0, 128, 127];
+    let mut compressed = Vec::new();
+    delta.compress(&input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    delta.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_delta_round_trip_signed_extremes() {
+    let delta = Delta::<i64>::new();
+    let input = vec![i64::MIN, i64::MAX, -1, 0, i64::MIN, 42];
+    let mut compressed = Vec::new();
+    delta.compress(&input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    delta.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_delta_empty() {
+    let delta = Delta::<u32>::new();
+    let mut compressed = Vec::new();
+    delta.compress(&[], &mut compressed);
+
+    let mut decompressed = Vec::new();
+    delta.decompress(&compressed, &mut decompressed);
+    assert!(decompressed.is_empty());
+}
+
+#[test]
+fn test_delta_small_negative_residuals() {
+    let delta = Delta::<i16>::new();
+    let input = vec![0i16, -1, -2, -3];
+    let mut compressed = Vec::new();
+    delta.compress(&input, &mut compressed);
+
+    // every residual is -1 after the first, which zigzags to 1
+    let residuals = compressed.chunks(2).map(bytemuck::pod_read_unaligned::<u16>).collect::<Vec<_>>();
+    assert_eq!(residuals, [0, 1, 1, 1]);
+}
+
+#[test]
+fn test_delta_with_inner_rle() {
+    let delta = Delta::<u32, RLE<u32>>::new();
+    let input = (0..10_000u32).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    delta.compress(&input, &mut compressed);
+    assert!(compressed.len() < 64);
+
+    let mut decompressed = Vec::new();
+    delta.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+// compresses and decompresses `input`, returns the compressed size
+// compares raw bytes, so that NaNs and -0.0 have to come back exactly too
+fn round_trip<C: Compressor>(compressor: &C, input: &[C::Input]) -> usize where C::Input: Pod {
+    let mut compressed = Vec::new();
+    compressor.compress(input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    compressor.decompress(&compressed, &mut decompressed);
+    assert_eq!(bytemuck::cast_slice::<_, u8>(&decompressed), bytemuck::cast_slice::<_, u8>(input));
+    compressed.len()
+}
+
+#[test]
+fn test_lookup_periodic() {
+    let input = (0..10_000u64).map(|i| i % 52).collect::<Vec<_>>();
+    let size = round_trip(&Lookup::new(), &input);
+    assert!(size < input.len());
+}
+
+#[test]
+fn test_lookup_with_phase_and_partial_window() {
+    // 3 leading elements throw off the alignment, and the last window is cut short
+    let mut input = vec![9u32, 8, 7];
+    input.extend((0..1003u32).map(|i| i % 5));
+    round_trip(&Lookup::new(), &input);
+}
+
+#[test]
+fn test_lookup_short_and_empty() {
+    round_trip(&Lookup::<u16>::new(), &[]);
+    round_trip(&Lookup::new(), &[1u16]);
+    round_trip(&Lookup::new(), &[1u8, 2, 3]);
+}
+
+#[test]
+fn test_lookup_unknown_entry() {
+    let lookup = Lookup::<u8>::new();
+
+    // window size 1, phase 0, a single entry [1], byte indices
+    let mut compressed = Vec::new();
+    for count in [1, 0, 1, 1] {
+        write_count_bytes(count, &mut compressed);
+    }
+    compressed.extend_from_slice(&[1, 0, 0, 200]);
+
+    let mut decompressed = Vec::new();
+    let result = lookup.try_decompress(&compressed, &mut decompressed);
+    assert_eq!(result, Err(CompressionError::UnknownEntry(200)));
+
+    // three single element entries need 2 bit indices, which leaves room for an index that doesn't exist
+    // 40 indices fill exactly 10 bytes, the last one is in the lowest bits
+    let input = (0..40).map(|i| (pseudo_random(i) % 3) as u8).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    lookup.compress(&input, &mut compressed);
+    *compressed.last_mut().unwrap() |= 0b11;
+
+    let result = lookup.try_decompress(&compressed, &mut decompressed);
+    assert_eq!(result, Err(CompressionError::UnknownEntry(3)));
+}
+
+#[test]
+fn test_hybrid_with_lookup_round_trip() {
+    let hybrid = Hybrid::<u64>::new().add::<VRLE<u64>>().add::<Lookup<u64>>();
+    let input = (0..5_000u64).map(|i| i % 7).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    hybrid.compress(&input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    hybrid.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_rle_over_lookup_round_trip() {
+    let rle = RLE::<u64, Lookup<u64>>::new();
+    let input = (0..20_000u64).map(|i| (i / 3) % 13).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    rle.compress(&input, &mut compressed);
+
+    let mut plain = Vec::new();
+    RLE::<u64>::new().compress(&input, &mut plain);
+    assert!(compressed.len() < plain.len());
+
+    let mut decompressed = Vec::new();
+    rle.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_vrle_over_delta_round_trip() {
+    let vrle = VRLE::<u32, Delta<u32, VRLE<u32>>>::new();
+    let input = (0..20_000u32).map(|i| (i / 4) * 10).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    vrle.compress(&input, &mut compressed);
+
+    let mut plain = Vec::new();
+    VRLE::<u32>::new().compress(&input, &mut plain);
+    assert!(compressed.len() * 2 < plain.len());
+
+    let mut decompressed = Vec::new();
+    vrle.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_rle_counts_through_inner_compressor() {
+    let rle = RLE::<u8, NaiveCompressor<u8>, VRLE<u8>>::new();
+    let input = (0..10_000u32).map(|i| (i / 100) as u8).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    rle.compress(&input, &mut compressed);
+
+    let mut plain = Vec::new();
+    RLE::<u8>::new().compress(&input, &mut plain);
+    assert!(compressed.len() < plain.len());
+
+    let mut decompressed = Vec::new();
+    rle.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_vrle_mismatched_runs() {
+    let vrle = VRLE::<u8>::new();
+    let mut compressed = Vec::new();
+    vrle.compress(&[1, 1, 2], &mut compressed);
+    compressed.push(3);
+
+    let mut decompressed = Vec::new();
+    let result = vrle.try_decompress(&compressed, &mut decompressed);
+    assert_eq!(result, Err(CompressionError::MismatchedRuns { counts: 2, values: 3 }));
+}
+
+#[test]
+fn test_pipeline_delta_rle() {
+    let compressor = pipeline::<i32>()
+        .then::<Differences<i32>>()
+        .finish::<VRLE<u32>>();
+
+    let input = (0..10_000i32).map(|i| 1_000 - i * 3).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    compressor.compress(&input, &mut compressed);
+    assert!(compressed.len() < 100);
+
+    let mut decompressed = Vec::new();
+    compressor.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_pipeline_chained_compressors() {
+    let compressor = pipeline::<u64>()
+        .then_compress::<RLE<u64>>()
+        .then::<Bytes<u8>>()
+        .finish::<VRLE<u8>>();
+
+    let input = (0..5_000u64).map(|i| i / 500).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    compressor.compress(&input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    compressor.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_pipeline_corrupted_stage() {
+    let compressor = pipeline::<u32>().then::<Bytes<u32>>().finish::<NaiveCompressor<u8>>();
+
+    let mut decompressed = Vec::new();
+    let result = compressor.try_decompress(&[1, 2, 3], &mut decompressed);
+    assert_eq!(result, Err(CompressionError::TrailingBytes(3)));
+}
+
+#[test]
+fn test_frame_decode_any_nested() {
+    let compressor = ParChunked::new_with(
+        Hybrid::<u64>::new()
+            .add::<RLE<u64>>()
+            .add::<VRLE<u64, Delta<u64>>>()
+            .add::<Lookup<u64>>(),
+        Some(1_000));
+
+    let input = (0..10_000u64).map(|i| i / 7).collect::<Vec<_>>();
+    let mut framed = Vec::new();
+    frame(&compressor, ElementKind::U64, &input, &mut framed);
+
+    let (header, elements) = decode_any(&framed).unwrap();
+    assert_eq!(header.kind, ElementKind::U64);
+    assert_eq!(header.count, input.len() as u64);
+    assert_eq!(header.descriptor, compressor.descriptor());
+    assert_eq!(elements, Elements::U64(input));
+}
+
+#[test]
+fn test_frame_signed_elements() {
+    let compressor = Delta::<i32, VRLE<u32>>::new();
+    let input = (0..1_000i32).map(|i| -i * 2).collect::<Vec<_>>();
+    let mut framed = Vec::new();
+    frame(&compressor, ElementKind::I32, &input, &mut framed);
+
+    let (header, elements) = decode_any(&framed).unwrap();
+    assert_eq!(header.kind, ElementKind::I32);
+    assert_eq!(elements.as_bytes(), bytemuck::cast_slice::<i32, u8>(&input));
+
+    let mut decompressed = Vec::new();
+    unframe(&compressor, &framed, &mut decompressed).unwrap();
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_frame_signed_bitpack() {
+    // BitPack offsets signed values from a sign flipped reference, decoding them as u32 would flip it back wrong
+    let input = [-5i32, -1, 0, 3, 100];
+    let mut framed = Vec::new();
+    frame(&BitPack::<i32>::new(), ElementKind::I32, &input, &mut framed);
+    let (_, elements) = decode_any(&framed).unwrap();
+    assert_eq!(elements.as_bytes(), bytemuck::cast_slice::<i32, u8>(&input));
+
+    let input = (-500..500i64).map(|i| i * 3).collect::<Vec<_>>();
+    let mut framed = Vec::new();
+    frame(&PFor::<i64>::new(), ElementKind::I64, &input, &mut framed);
+    let (_, elements) = decode_any(&framed).unwrap();
+    assert_eq!(elements.as_bytes(), bytemuck::cast_slice::<i64, u8>(&input));
+}
+
+#[test]
+fn test_unframe_rejects_other_compressor() {
+    let mut framed = Vec::new();
+    frame(&RLE::<u16>::new(), ElementKind::U16, &[1, 1, 2], &mut framed);
+
+    let mut decompressed = Vec::new();
+    let result = unframe(&VRLE::<u16>::new(), &framed, &mut decompressed);
+    assert!(matches!(result, Err(CompressionError::InvalidHeader(_))));
+}
+
+#[test]
+fn test_decode_any_corrupted_header() {
+    let mut framed = Vec::new();
+    frame(&VRLE::<u8>::new(), ElementKind::U8, &[1, 1, 2], &mut framed);
+
+    let mut bad_magic = framed.clone();
+    bad_magic[0] = b'X';
+    assert!(matches!(decode_any(&bad_magic), Err(CompressionError::InvalidHeader(_))));
+
+    let mut bad_count = framed.clone();
+    bad_count[7] = 4;
+    assert_eq!(decode_any(&bad_count), Err(CompressionError::LengthMismatch { expected: 4, actual: 3 }));
+
+    let mut bad_algorithm = framed.clone();
+    bad_algorithm[15] = 200;
+    assert_eq!(decode_any(&bad_algorithm), Err(CompressionError::UnsupportedAlgorithm(200)));
+
+    assert_eq!(decode_any(&framed[..10]), Err(CompressionError::Truncated));
+}
+
+#[test]
+fn test_parchunked_chunk_table_layout() {
+    let par_rle = ParChunked { compressor: RLE::<u8>::new(), chunk_size: Some(4) };
+    let input = vec![1u8, 1, 2, 2, 3, 3, 4, 4];
+    let mut compressed = Vec::new();
+    par_rle.compress(&input, &mut compressed);
+
+    // count layout, table length, then the (offset, count, elements) triples of both chunks
+    let layout = CountLayout::from_u8(compressed[0]).unwrap();
+    let table_length = u64::from_le_bytes(compressed[1..9].try_into().unwrap()) as usize;
+    let table = layout.try_read_counts(&compressed[9..(9 + table_length)]).unwrap();
+    assert_eq!(table.len(), 6);
+    assert_eq!(table[0], 0);
+    assert_eq!(table[2], 4);
+    assert_eq!(table[3], table[1]);
+}
+
+#[test]
+fn test_rle_little_endian_counts() {
+    let rle = RLE::<u8>::new();
+    let mut compressed = Vec::new();
+    rle.compress(&[5u8; 300], &mut compressed);
+
+    // counts section length, then the single count and the single value
+    let mut expected = vec![0, 8];
+    expected.extend_from_slice(&300u64.to_le_bytes());
+    expected.push(5);
+    assert_eq!(compressed, expected);
+}
+
+#[test]
+fn test_stream_round_trip_elements() {
+    let input = (0..10_000u32).map(|i| i / 64).collect::<Vec<_>>();
+    let mut writer = CompressWriter::new(Vec::new(), VRLE::<u32>::new()).with_block_size(1_000);
+    for part in input.chunks(333) {
+        writer.write_elements(part).unwrap();
+    }
+    let compressed = writer.finish().unwrap();
+
+    let mut reader = DecompressReader::new(compressed.as_slice(), VRLE::<u32>::new());
+    let mut decompressed = Vec::new();
+    let mut blocks = 0;
+    while let Some(block) = reader.read_block().unwrap() {
+        assert!(block.len() <= 1_000);
+        decompressed.extend(block);
+        blocks += 1;
+    }
+
+    assert_eq!(blocks, 10);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_stream_io_copy() {
+    let input = (0..50_000u64).map(|i| (i / 100) % 3).collect::<Vec<_>>();
+    let bytes = bytemuck::cast_slice::<u64, u8>(&input);
+
+    let mut writer = CompressWriter::new(Vec::new(), ParChunked::<RLE<u64>>::new()).with_block_size(4_096);
+    std::io::copy(&mut &bytes[..], &mut writer).unwrap();
+    let compressed = writer.finish().unwrap();
+    assert!(compressed.len() < bytes.len() / 10);
+
+    let mut reader = DecompressReader::new(compressed.as_slice(), ParChunked::<RLE<u64>>::new());
+    let mut decompressed = Vec::new();
+    std::io::Read::read_to_end(&mut reader, &mut decompressed).unwrap();
+    assert_eq!(decompressed, bytes);
+}
+
+#[test]
+fn test_stream_partial_element_and_truncation() {
+    let mut writer = CompressWriter::new(Vec::new(), RLE::<u32>::new());
+    std::io::Write::write_all(&mut writer, &[1, 2, 3, 4, 5]).unwrap();
+    assert!(writer.finish().is_err());
+
+    let mut writer = CompressWriter::new(Vec::new(), RLE::<u32>::new());
+    writer.write_elements(&[1, 1, 1]).unwrap();
+    let compressed = writer.finish().unwrap();
+
+    let mut reader = DecompressReader::new(&compressed[..compressed.len() - 1], RLE::<u32>::new());
+    let err = reader.read_block().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_parchunked_decompress_range() {
+    let par_vrle = ParChunked::new_with(VRLE::<u32>::new(), Some(1_000));
+    let input = (0..10_500u32).map(|i| i / 10).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    par_vrle.compress(&input, &mut compressed);
+
+    assert_eq!(par_vrle.len(&compressed).unwrap(), 10_500);
+    assert_eq!(par_vrle.decompress_range(&compressed, 0..10_500).unwrap(), input);
+    assert_eq!(par_vrle.decompress_range(&compressed, 999..1_001).unwrap(), &input[999..1_001]);
+    assert_eq!(par_vrle.decompress_range(&compressed, 2_345..7_890).unwrap(), &input[2_345..7_890]);
+    assert_eq!(par_vrle.decompress_range(&compressed, 10_400..10_500).unwrap(), &input[10_400..10_500]);
+    assert!(par_vrle.decompress_range(&compressed, 5..5).unwrap().is_empty());
+}
+
+#[test]
+fn test_parchunked_get() {
+    let par_rle = ParChunked::new_with(RLE::<u64>::new(), Some(64));
+    let input = (0..1_000u64).map(|i| i * i).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    par_rle.compress(&input, &mut compressed);
+
+    for index in [0, 63, 64, 500, 999] {
+        assert_eq!(par_rle.get(&compressed, index).unwrap(), input[index as usize]);
+    }
+
+    let result = par_rle.get(&compressed, 1_000);
+    assert_eq!(result, Err(CompressionError::RangeOutOfBounds { start: 1_000, end: 1_001, len: 1_000 }));
+}
+
+#[test]
+fn test_lz_periodic_modulo() {
+    let input = (0..100_000u64).map(|i| i % 52).collect::<Vec<_>>();
+    let size = round_trip(&LZ::new(), &input);
+    assert!(size < 52 * 8 + 64);
+}
+
+#[test]
+fn test_lz_sine() {
+    let input = (0..50_000).map(|i| ((i as f32 * std::f32::consts::PI / 2.0).sin() * 20.0) as i32).collect::<Vec<_>>();
+    let size = round_trip(&LZ::new(), &input);
+    assert!(size * 100 < input.len() * 4);
+}
+
+#[test]
+fn test_lz_unaligned_repeats_and_floats() {
+    let mut input = vec![0.5f32, -0.0, f32::NAN];
+    for i in 0..2_000 {
+        input.push((i % 17) as f32 * 0.25);
+        if i % 101 == 0 {
+            input.push(-0.0);
+        }
+    }
+
+    let lz = LZ::<f32>::new();
+    let mut compressed = Vec::new();
+    lz.compress(&input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    lz.decompress(&compressed, &mut decompressed);
+    assert_eq!(bytemuck::cast_slice::<f32, u32>(&decompressed), bytemuck::cast_slice::<f32, u32>(&input));
+}
+
+#[test]
+fn test_lz_short_and_empty() {
+    round_trip(&LZ::<u8>::new(), &[]);
+    round_trip(&LZ::new(), &[7u8]);
+    round_trip(&LZ::new(), &[1u16, 2, 3, 1, 2, 3, 1, 2]);
+}
+
+#[test]
+fn test_lz_invalid_offset() {
+    let lz = LZ::<u8>::new();
+    let mut compressed = Vec::new();
+    write_count_bytes(1, &mut compressed);
+    compressed.push(9);
+    write_count_bytes(4, &mut compressed);
+    write_count_bytes(2, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    let result = lz.try_decompress(&compressed, &mut decompressed);
+    assert_eq!(result, Err(CompressionError::MatchOutOfRange { offset: 2, available: 1 }));
+}
+
+#[test]
+fn test_lz_hostile_match_length() {
+    let lz = LZ::<u8>::new();
+    let mut compressed = Vec::new();
+    write_count_bytes(1, &mut compressed);
+    compressed.push(9);
+    write_count_bytes(1 << 36, &mut compressed);
+    write_count_bytes(1, &mut compressed);
+    assert_eq!(compressed.len(), 14);
+
+    let result = lz.try_decompress_bounded(&compressed, &mut Vec::new(), 1 << 20);
+    assert_eq!(result, Err(CompressionError::OutputTooLarge { requested: (1 << 36) + 1, limit: 1 << 20 }));
+
+    // a long overlapping match within the limit still repeats its period
+    let mut compressed = Vec::new();
+    write_count_bytes(3, &mut compressed);
+    compressed.extend([1, 2, 3]);
+    write_count_bytes(1000, &mut compressed);
+    write_count_bytes(3, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    lz.try_decompress(&compressed, &mut decompressed).unwrap();
+    assert_eq!(decompressed, [1u8, 2, 3].repeat(335)[..1003]);
+}
+
+#[test]
+fn test_huffman_skewed() {
+    // mostly zeros with the occasional small value, like the count bytes of VRLE
+    let input = (0..10000u32).map(|i| if i % 7 == 0 { (i % 5) as u8 } else { 0 }).collect::<Vec<_>>();
+    let size = round_trip(&Huffman::new(), &input);
+    assert!(size < input.len() / 4, "huffman didn't compress: {size}");
+}
+
+#[test]
+fn test_huffman_all_symbols_and_wide_elements() {
+    let input = (0..=255u8).cycle().take(5000).collect::<Vec<_>>();
+    round_trip(&Huffman::new(), &input);
+
+    // fibonacci-like frequencies force the code lengths past the limit before flattening
+    let mut skewed = Vec::new();
+    let (mut a, mut b) = (1usize, 1usize);
+    for symbol in 0..30u8 {
+        skewed.extend(std::iter::repeat_n(symbol, a));
+        (a, b) = (b, a + b);
+    }
+    round_trip(&Huffman::new(), &skewed);
+
+    round_trip(&Huffman::new(), &(0..1000u32).map(|i| i % 3).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_huffman_empty_and_single_symbol() {
+    round_trip(&Huffman::<u8>::new(), &[]);
+    round_trip(&Huffman::new(), &[42u8]);
+    let size = round_trip(&Huffman::new(), &[42u8; 1000]);
+    assert!(size < 200);
+}
+
+#[test]
+fn test_huffman_inner_of_run_length() {
+    let input = (0..2000u32).map(|i| (i / 3 % 4) as u16).collect::<Vec<_>>();
+
+    let plain = VRLE::<u16>::new();
+    let mut plain_compressed = Vec::new();
+    plain.compress(&input, &mut plain_compressed);
+
+    let entropy = VRLE::<u16, Huffman<u16>, Huffman>::new_with_counts(Huffman::new(), Huffman::new());
+    let mut entropy_compressed = Vec::new();
+    entropy.compress(&input, &mut entropy_compressed);
+    assert!(entropy_compressed.len() < plain_compressed.len() / 2);
+
+    let mut decompressed = Vec::new();
+    entropy.decompress(&entropy_compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+
+    // the counts stream goes through huffman when the stack is built from a descriptor
+    let rebuilt = build::<u16>(&entropy.descriptor()).unwrap();
+    let mut decompressed = Vec::new();
+    rebuilt.decompress(&entropy_compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_huffman_truncated() {
+    let huffman = Huffman::<u8>::new();
+    let mut compressed = Vec::new();
+    huffman.compress(b"abracadabra", &mut compressed);
+    compressed.truncate(compressed.len() - 1);
+
+    let mut decompressed = Vec::new();
+    assert_eq!(huffman.try_decompress(&compressed, &mut decompressed), Err(CompressionError::Truncated));
+}
+
+#[test]
+fn test_bitstream_trailing_bytes() {
+    // the bitstream runs up to the end of the buffer, anything after its last byte is rejected
+    let mut compressed = Vec::new();
+    Huffman::<u8>::new().compress(b"abracadabra", &mut compressed);
+    compressed.push(0);
+    assert_eq!(Huffman::<u8>::new().try_decompress(&compressed, &mut Vec::new()), Err(CompressionError::TrailingBytes(1)));
+
+    let mut compressed = Vec::new();
+    Gorilla::<f64>::new().compress(&[1.0, 1.5, 1.5, 2.0], &mut compressed);
+    compressed.extend([0, 0]);
+    assert_eq!(Gorilla::<f64>::new().try_decompress(&compressed, &mut Vec::new()), Err(CompressionError::TrailingBytes(2)));
+
+    let mut compressed = Vec::new();
+    Buckets::<u64>::new().compress(&[0, 100, 500], &mut compressed);
+    compressed.push(0xff);
+    assert_eq!(Buckets::<u64>::new().try_decompress(&compressed, &mut Vec::new()), Err(CompressionError::TrailingBytes(1)));
+
+    // with no elements at all, there is no bitstream either
+    for empty in [&[0, 0, 0][..], &[0, 0, 7]] {
+        assert_eq!(Huffman::<u8>::new().try_decompress(empty, &mut Vec::new()), Err(CompressionError::TrailingBytes(1)));
+        assert_eq!(Gorilla::<f32>::new().try_decompress(empty, &mut Vec::new()), Err(CompressionError::TrailingBytes(1)));
+        assert_eq!(Buckets::<u32>::new().try_decompress(empty, &mut Vec::new()), Err(CompressionError::TrailingBytes(1)));
+    }
+}
+
+#[test]
+fn test_arithmetic_beats_huffman_on_small_alphabet() {
+    // two symbols at 95/5: huffman needs a whole bit per symbol, the entropy is below 0.3 bits
+    let mut state = 12345u32;
+    let input = (0..20000).map(|_| {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (state >> 16).is_multiple_of(20) as u8
+    }).collect::<Vec<_>>();
+
+    let huffman = round_trip(&Huffman::new(), &input);
+    let arithmetic = round_trip(&Arithmetic::<u8, Order0>::new(), &input);
+    assert!(arithmetic * 2 < huffman, "arithmetic {arithmetic} vs huffman {huffman}");
+}
+
+#[test]
+fn test_arithmetic_order1_uses_previous_byte() {
+    // every byte is determined by the one before it, but all 256 values are equally common
+    let input = (0..20000u32).map(|i| (i.wrapping_mul(97) % 256) as u8).collect::<Vec<_>>();
+
+    let order0 = round_trip(&Arithmetic::<u8, Order0>::new(), &input);
+    let order1 = round_trip(&Arithmetic::<u8, Order1>::new(), &input);
+    assert!(order0 > input.len() * 9 / 10);
+    assert!(order1 * 2 < order0, "order1 {order1} vs order0 {order0}");
+}
+
+#[test]
+fn test_arithmetic_edge_cases() {
+    round_trip(&Arithmetic::<u8, Order0>::new(), &[]);
+    round_trip(&Arithmetic::<u8, Order1>::new(), &[200]);
+    round_trip(&Arithmetic::<u8, Order0>::new(), &[0xff; 5000]);
+    round_trip(&Arithmetic::<u8, Order1>::new(), &(0..=255u8).cycle().take(3000).collect::<Vec<_>>());
+    round_trip(&Arithmetic::<u32, Order1>::new(), &(0..3000u32).map(|i| i * i).collect::<Vec<_>>());
+
+    let arithmetic = Arithmetic::<u8, Order1>::new();
+    let mut compressed = Vec::new();
+    arithmetic.compress(b"hello hello hello", &mut compressed);
+    compressed.truncate(compressed.len() - 3);
+    assert_eq!(arithmetic.try_decompress(&compressed, &mut Vec::new()), Err(CompressionError::Truncated));
+}
+
+#[test]
+fn test_arithmetic_after_delta() {
+    let input = (0..5000i32).map(|i| i * 3 + (i % 4)).collect::<Vec<_>>();
+    let delta = Delta::<i32, Arithmetic<u32, Order1>>::new();
+
+    let mut compressed = Vec::new();
+    delta.compress(&input, &mut compressed);
+    assert!(compressed.len() < input.len() / 2);
+
+    let rebuilt = build::<i32>(&delta.descriptor()).unwrap();
+    let mut decompressed = Vec::new();
+    rebuilt.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+fn pseudo_random(seed: u32) -> u32 {
+    let mut value = seed;
+    value ^= value << 13;
+    value ^= value >> 17;
+    value ^= value << 5;
+    value = value.wrapping_mul(0x322adf);
+    value ^= value >> 11;
+    value.wrapping_add(0x9e3779b9)
+}
+
+#[test]
+fn test_bitpack_narrow_values() {
+    // 12 bit sensor readings around an offset
+    let input = (0..10000u32).map(|i| 1_000_000 + pseudo_random(i) % 4096).collect::<Vec<_>>();
+    let size = round_trip(&BitPack::<u32>::new(), &input);
+    assert!(size < input.len() * 12 / 8 + input.len() / 16, "bitpack size {size}");
+}
+
+#[test]
+fn test_bitpack_signed_and_extremes() {
+    round_trip(&BitPack::<i16>::new(), &(-500..500i16).collect::<Vec<_>>());
+    round_trip(&BitPack::<i64>::new(), &[i64::MIN, i64::MAX, 0, -1, 1]);
+    round_trip(&BitPack::<u64>::new(), &[u64::MAX, 0, u64::MAX / 3]);
+    round_trip(&BitPack::<u8>::new_with(3), &(0..=255u8).collect::<Vec<_>>());
+    round_trip(&BitPack::<u32>::new(), &[7u32; 1000]);
+    round_trip(&BitPack::<u32>::new(), &[]);
+}
+
+#[test]
+fn test_pfor_outliers() {
+    // small values with the occasional huge outlier
+    let input = (0..10000u32).map(|i| if i % 97 == 0 { 1 << 30 } else { pseudo_random(i) % 16 }).collect::<Vec<_>>();
+
+    let packed = round_trip(&BitPack::<u32>::new(), &input);
+    let patched = round_trip(&PFor::<u32>::new(), &input);
+    assert!(patched * 3 < packed, "pfor {patched} vs bitpack {packed}");
+
+    round_trip(&PFor::<i64>::new(), &[i64::MIN, 0, 1, 2, 3, i64::MAX, 5, 6, 7]);
+    round_trip(&PFor::<u64>::new_with(4), &[u64::MAX, 1, 2, 3, 0, 1, 2, u64::MAX]);
+}
+
+#[test]
+fn test_bitpack_corrupt_input() {
+    let mut compressed = Vec::new();
+    BitPack::<u16>::new().compress(&[1u16, 2, 3, 40000], &mut compressed);
+
+    // the width byte comes right before the 4 packed 16 bit offsets
+    let mut wide = compressed.clone();
+    let width = wide.len() - 9;
+    assert_eq!(wide[width], 16);
+    wide[width] = 17;
+    assert!(matches!(BitPack::<u16>::new().try_decompress(&wide, &mut Vec::new()), Err(CompressionError::InvalidHeader(_))));
+
+    compressed.pop();
+    assert_eq!(BitPack::<u16>::new().try_decompress(&compressed, &mut Vec::new()), Err(CompressionError::Truncated));
+}
+
+#[test]
+fn test_bitpack_hostile_counts() {
+    // u64::MAX elements in blocks of u64::MAX, and a single block of width 0 that needs no packed bytes
+    let mut hostile = [3].into_iter().chain(u64::MAX.to_le_bytes()).collect::<Vec<u8>>();
+    hostile.extend([3].into_iter().chain(u64::MAX.to_le_bytes()));
+    hostile.extend([0, 0, 0]);
+    assert_eq!(hostile.len(), 21);
+
+    assert!(matches!(BitPack::<u32>::new().try_decompress(&hostile, &mut Vec::new()), Err(CompressionError::OutputTooLarge { .. })));
+    assert!(matches!(PFor::<u32>::new().try_decompress(&hostile, &mut Vec::new()), Err(CompressionError::OutputTooLarge { .. })));
+
+    // a plausible count whose single block claims more packed bytes than there are
+    let mut long = vec![0, 100, 0, 100, 0, 0, 64];
+    assert_eq!(BitPack::<u64>::new().try_decompress(&long, &mut Vec::new()), Err(CompressionError::Truncated));
+    long.extend([0u8; 800]);
+    assert!(BitPack::<u64>::new().try_decompress(&long, &mut Vec::new()).is_ok());
+}
+
+#[test]
+fn test_vrle_gamma_counts() {
+    // lots of short runs, where the mode byte of every count costs the most
+    let input = (0..5000u32).map(|i| (pseudo_random(i / 3) % 4) as u16).collect::<Vec<_>>();
+
+    let bytes = VRLE::<u16>::new();
+    let gamma = VRLE::<u16>::new().with_layout(CountLayout::Gamma);
+
+    let mut bytes_compressed = Vec::new();
+    bytes.compress(&input, &mut bytes_compressed);
+    let mut gamma_compressed = Vec::new();
+    gamma.compress(&input, &mut gamma_compressed);
+    assert!(gamma_compressed.len() * 3 < bytes_compressed.len() * 2);
+
+    // the layout is read from the header, so any VRLE decodes it
+    let mut decompressed = Vec::new();
+    bytes.decompress(&gamma_compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+
+    let empty = VRLE::<u16>::new().with_layout(CountLayout::Gamma);
+    let mut compressed = Vec::new();
+    empty.compress(&[], &mut compressed);
+    let mut decompressed = Vec::new();
+    empty.decompress(&compressed, &mut decompressed);
+    assert!(decompressed.is_empty());
+}
+
+#[test]
+fn test_lookup_bit_packed_indices() {
+    // 5 distinct values need 3 bit indices instead of a whole byte each
+    let input = (0..4000).map(|i| pseudo_random(i) % 5).collect::<Vec<_>>();
+    let size = round_trip(&Lookup::new(), &input);
+    assert!(size < input.len() * 3 / 8 + 64, "lookup size {size}");
+}
+
+#[test]
+fn test_lookup_hostile_index_count() {
+    // a single entry needs indices of width 0, which take no bits at all
+    let size = round_trip(&Lookup::new(), &[7u32; 10_000]);
+    assert!(size < 64, "lookup size {size}");
+
+    // window size 1, phase 0, a single entry [7], bit packed indices of width 0, and 2^40 of them
+    let mut hostile = Vec::new();
+    for count in [1, 0, 1, 1] {
+        write_count_bytes(count, &mut hostile);
+    }
+    hostile.extend([7, 4, 0]);
+    write_count_bytes(1 << 40, &mut hostile);
+
+    let result = Lookup::<u8>::new().try_decompress_bounded(&hostile, &mut Vec::new(), 1 << 20);
+    assert_eq!(result, Err(CompressionError::OutputTooLarge { requested: 1 << 40, limit: 1 << 20 }));
+}
+
+#[test]
+fn test_lookup_long_entries() {
+    // window size 1000, phase 0, two entries of 1000 elements each, then 3000 indices of one byte or one bit
+    let mut header = Vec::new();
+    for count in [1000, 0, 2] {
+        write_count_bytes(count, &mut header);
+    }
+    for value in [1u8, 2] {
+        write_count_bytes(1000, &mut header);
+        header.extend([value; 1000]);
+    }
+
+    let bytes = [&header[..], &[0], &[1; 3000]].concat();
+    let mut bit_packed = [&header[..], &[4, 1]].concat();
+    write_count_bytes(3000, &mut bit_packed);
+    bit_packed.extend([0xff; 375]);
+
+    for hostile in [bytes, bit_packed] {
+        // every index is checked before its entry is copied, the output never grows past the limit
+        let mut decompressed = Vec::new();
+        let result = Lookup::<u8>::new().try_decompress_bounded(&hostile, &mut decompressed, 100_500);
+        assert_eq!(result, Err(CompressionError::OutputTooLarge { requested: 101_000, limit: 100_500 }));
+        assert_eq!(decompressed.len(), 100_000);
+
+        let mut decompressed = Vec::new();
+        Lookup::<u8>::new().try_decompress_bounded(&hostile, &mut decompressed, 3_000_000).unwrap();
+        assert_eq!(decompressed, vec![2; 3_000_000]);
+    }
+}
+
+#[test]
+fn test_gorilla_slow_telemetry() {
+    // a slowly changing reading, repeated for a while between updates like a sampled sensor
+    let input = (0..10_000).map(|i| 20.0 + ((i / 10) as f64 * 0.01).sin()).collect::<Vec<_>>();
+    let size = round_trip(&Gorilla::new(), &input);
+    assert!(size * 3 < size_of_val(input.as_slice()), "gorilla size {size}");
+
+    let input = (0..10_000).map(|i| (i / 4) as f32 * 0.5).collect::<Vec<_>>();
+    let size = round_trip(&Gorilla::new(), &input);
+    assert!(size * 3 < size_of_val(input.as_slice()), "gorilla size {size}");
+}
+
+#[test]
+fn test_gorilla_sine_and_special_values() {
+    round_trip(&Gorilla::new(), &(0..1000).map(|i| (i as f32 * std::f32::consts::PI / 2.0).sin() * 20.0).collect::<Vec<_>>());
+    round_trip(&Gorilla::new(), &[f64::NAN, -0.0, 0.0, f64::INFINITY, f64::NEG_INFINITY, f64::MIN_POSITIVE, f64::MAX, f64::NAN]);
+    round_trip(&Gorilla::new(), &[f32::from_bits(0x7fc0_0001), f32::MIN, 1.0, 1.0, 1.0, -1.0]);
+    round_trip(&Gorilla::<f64>::new(), &[]);
+    round_trip(&Gorilla::new(), &[1.5f32]);
+
+    // the raw bits of integers work too, which is how containers store floats
+    round_trip(&Gorilla::new(), &[u64::MAX, 0, 1 << 63, 1, u64::MAX]);
+}
+
+#[test]
+fn test_gorilla_descriptor_and_corrupt_input() {
+    let gorilla = Gorilla::<f32>::new();
+    let mut compressed = Vec::new();
+    gorilla.compress(&[1.0f32, 2.0, 3.0, 4.0], &mut compressed);
+
+    let rebuilt = build::<u32>(&gorilla.descriptor()).unwrap();
+    let mut decompressed = Vec::new();
+    rebuilt.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, [1.0f32, 2.0, 3.0, 4.0].map(f32::to_bits));
+    assert!(build::<u16>(&gorilla.descriptor()).is_err());
+    assert!(round_trip(&build::<i64>(&gorilla.descriptor()).unwrap(), &[-1, -1, 7, i64::MIN]) > 0);
+
+    // a reused window before any window was opened
+    let mut corrupt = Vec::new();
+    write_count_bytes(2, &mut corrupt);
+    corrupt.extend_from_slice(&[0, 0, 0, 0, 0b1000_0000]);
+    assert_eq!(gorilla.try_decompress(&corrupt, &mut Vec::new()), Err(CompressionError::InvalidCode));
+
+    compressed.pop();
+    assert_eq!(gorilla.try_decompress(&compressed, &mut Vec::new()), Err(CompressionError::Truncated));
+}
+
+fn timestamps(count: usize, jitter: bool) -> Vec<i64> {
+    (0..count as u32)
+        .map(|i| 1_700_000_000_000 + i as i64 * 1_000 + if jitter { (pseudo_random(i) % 7) as i64 - 3 } else { 0 })
+        .collect()
+}
+
+#[test]
+fn test_delta_of_delta_regular_series() {
+    let input = timestamps(10_000, false);
+
+    // one bit per element once the interval is known
+    let buckets = DeltaOfDelta::<i64>::new();
+    let mut compressed = Vec::new();
+    buckets.compress(&input, &mut compressed);
+    assert!(compressed.len() < input.len() / 8 + 32, "buckets size {}", compressed.len());
+
+    let mut decompressed = Vec::new();
+    buckets.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+
+    // and RLE folds the zeros into a single run
+    let rle = DeltaOfDelta::<i64, RLE<u64>>::new();
+    let mut compressed = Vec::new();
+    rle.compress(&input, &mut compressed);
+    assert!(compressed.len() < 64, "rle size {}", compressed.len());
+
+    let mut decompressed = Vec::new();
+    rle.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_delta_of_delta_jitter_and_extremes() {
+    let input = timestamps(10_000, true);
+    let dod = DeltaOfDelta::<i64>::new();
+    let mut compressed = Vec::new();
+    dod.compress(&input, &mut compressed);
+    assert!(compressed.len() * 4 < size_of_val(input.as_slice()), "jittered size {}", compressed.len());
+
+    let rebuilt = build::<i64>(&dod.descriptor()).unwrap();
+    let mut decompressed = Vec::new();
+    rebuilt.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+
+    for input in [vec![u64::MAX, 0, u64::MAX, 1, 1 << 63], vec![], vec![42]] {
+        let dod = DeltaOfDelta::<u64>::new();
+        let mut compressed = Vec::new();
+        dod.compress(&input, &mut compressed);
+
+        let mut decompressed = Vec::new();
+        dod.decompress(&compressed, &mut decompressed);
+        assert_eq!(decompressed, input);
+    }
+}
+
+#[test]
+fn test_buckets_truncated() {
+    let buckets = Buckets::<u64>::new();
+    let mut compressed = Vec::new();
+    buckets.compress(&[0, 100, 500, 4000, 1 << 40], &mut compressed);
+
+    let mut decompressed = Vec::new();
+    buckets.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, [0, 100, 500, 4000, 1 << 40]);
+
+    compressed.pop();
+    assert_eq!(buckets.try_decompress(&compressed, &mut Vec::new()), Err(CompressionError::Truncated));
+}
+
+#[test]
+fn test_shuffle_exact_inverse() {
+    for count in [0, 1, 7, 8, 9, 100, 1001] {
+        let points = (0..count).map(|i| Point { x: pseudo_random(i), y: i }).collect::<Vec<_>>();
+        round_trip(&Shuffle::<Point>::new(), &points);
+        round_trip(&BitShuffle::<Point>::new(), &points);
+
+        let bytes = (0..count).map(|i| pseudo_random(i) as u8).collect::<Vec<_>>();
+        round_trip(&Shuffle::<u8>::new(), &bytes);
+        round_trip(&BitShuffle::<u8>::new(), &bytes);
+    }
+
+    // output is exactly as long as the input, even with a partial group of 8
+    let mut shuffled = Vec::new();
+    shuffle_bits(&[1u16, 2, 3, 4, 5, 6, 7, 8, 9], &mut shuffled);
+    assert_eq!(shuffled.len(), 18);
+    assert_eq!(shuffled[0], 0b0101_0101);
+}
+
+#[test]
+fn test_shuffle_groups_high_bytes() {
+    // small coordinates: the high bytes are always zero but sit between noisy low bytes
+    let points = (0..10_000).map(|i| Point { x: pseudo_random(i) % 256, y: pseudo_random(i + 1) % 1024 }).collect::<Vec<_>>();
+
+    let plain = round_trip(&LZ::<u8>::new(), bytemuck::cast_slice::<Point, u8>(&points));
+    let shuffled = round_trip(&Shuffle::<Point, LZ<u8>>::new(), &points);
+    let bit_shuffled = round_trip(&BitShuffle::<Point, LZ<u8>>::new(), &points);
+    assert!(shuffled * 2 < plain, "shuffled {shuffled} vs plain {plain}");
+    assert!(bit_shuffled * 2 < plain, "bit shuffled {bit_shuffled} vs plain {plain}");
+
+    let rebuilt = build::<u64>(&BitShuffle::<u64, LZ<u8>>::new().descriptor()).unwrap();
+    let input = points.iter().map(|point| bytemuck::cast::<Point, u64>(*point)).collect::<Vec<_>>();
+    round_trip(&rebuilt, &input);
+}
+
+#[test]
+fn test_shuffle_pipeline_stages() {
+    let input = (0..5_000u32).map(|i| i * 3).collect::<Vec<_>>();
+    let bytes = pipeline::<u32>().then::<ShuffledBytes<u32>>().finish::<VRLE<u8>>();
+    let bits = pipeline::<u32>().then::<ShuffledBits<u32>>().finish::<LZ<u8>>();
+
+    round_trip(&bytes, &input);
+    round_trip(&bits, &input);
+
+    let mut decompressed = Vec::new();
+    assert_eq!(ShuffledBytes::<u32>::new().try_backward(&[1, 2, 3], &mut decompressed, usize::MAX), Err(CompressionError::Truncated));
+}
+
+columns!(Point { x, y });
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, PartialEq, Debug, Zeroable)]
+struct Reading {
+    timestamp: u64,
+    value: u32,
+    sensor: u16,
+    tag: [u8; 2],
+}
+
+columns!(Reading { timestamp, value, sensor, tag });
+
+#[test]
+fn test_columnar_round_trip() {
+    assert_eq!(Point::columns(), vec![0..4, 4..8]);
+    assert_eq!(Reading::columns(), vec![0..8, 8..12, 12..14, 14..16]);
+
+    for count in [0, 1, 2, 1000] {
+        let readings = (0..count).map(|i| Reading {
+            timestamp: 1_700_000_000 + 10 * i as u64,
+            value: pseudo_random(i) % 100,
+            sensor: (i / 100) as u16,
+            tag: [b'a', (i % 3) as u8],
+        }).collect::<Vec<_>>();
+        round_trip(&Columnar::<Reading>::new(), &readings);
+
+        let rebuilt = Columnar::<Reading>::from_descriptor(&Columnar::<Reading>::new().descriptor()).unwrap();
+        round_trip(&rebuilt, &readings);
+    }
+}
+
+#[test]
+fn test_columnar_beats_whole_structs() {
+    // x changes rarely and y counts up, so RLE on whole points never finds a run
+    let points = (0..10_000).map(|i| Point { x: i / 1000, y: i }).collect::<Vec<_>>();
+
+    let whole = round_trip(&RLE::<Point>::new(), &points);
+    let rle = RLE::<u32>::new().descriptor();
+    let delta = Descriptor::node(AlgorithmId::Delta, vec![rle.clone()]);
+    let columnar = round_trip(&Columnar::<Point>::new_with(&[rle, delta]).unwrap(), &points);
+    assert!(columnar * 100 < whole, "columnar {columnar} vs whole {whole}");
+}
+
+#[test]
+fn test_columnar_invalid_input() {
+    let rle = RLE::<u32>::new().descriptor();
+    assert_eq!(Columnar::<Point>::new_with(std::slice::from_ref(&rle)).err(), Some(CompressionError::InvalidHeader("wrong number of column stacks")));
+    assert!(build::<u64>(&Columnar::<Point>::new().descriptor()).is_err());
+
+    let columnar = Columnar::<Point>::new_with(&[rle.clone(), rle]).unwrap();
+    let points = (0..100).map(|i| Point { x: i, y: i % 7 }).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    columnar.compress(&points, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    assert_eq!(columnar.try_decompress(&compressed[..compressed.len() - 1], &mut decompressed), Err(CompressionError::Truncated));
+
+    // a second column with fewer rows than the first
+    let mut short = Vec::new();
+    columnar.compress(&points[..50], &mut short);
+    let section_end = |compressed: &[u8]| {
+        let (length, bytes_read) = try_read_count_bytes(compressed).unwrap();
+        length as usize + bytes_read
+    };
+    let mut mixed = compressed[..section_end(&compressed)].to_vec();
+    mixed.extend_from_slice(&short[section_end(&short)..]);
+    assert_eq!(columnar.try_decompress(&mixed, &mut decompressed), Err(CompressionError::LengthMismatch { expected: 100, actual: 50 }));
+
+    compressed.push(0);
+    assert_eq!(columnar.try_decompress(&compressed, &mut decompressed), Err(CompressionError::TrailingBytes(1)));
+}
+
+fn hybrid_candidates() -> Hybrid<u32> {
+    Hybrid::<u32>::new()
+        .add::<NaiveCompressor<u32>>()
+        .add::<RLE<u32>>()
+        .add::<LZ<u32>>()
+        .add::<BitPack<u32>>()
+        .add::<Delta<u32, BitPack<u32>>>()
+}
+
+#[test]
+fn test_hybrid_parallel_matches_sequential() {
+    let pool = std::sync::Arc::new(rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap());
+    let inputs = [
+        (0..10_000u32).collect::<Vec<_>>(),
+        (0..10_000u32).map(|i| i / 500).collect(),
+        (0..10_000u32).map(pseudo_random).collect(),
+        (0..10_000u32).map(|i| pseudo_random(i) % 16).collect(),
+        vec![],
+    ];
+
+    for input in inputs.iter() {
+        let sequential = hybrid_candidates().with_parallel(false);
+        let mut expected = Vec::new();
+        sequential.compress(input, &mut expected);
+
+        let hybrids = [
+            hybrid_candidates(),
+            hybrid_candidates().with_thread_pool(pool.clone()),
+            hybrid_candidates().with_size_bound(true),
+            hybrid_candidates().with_parallel(false).with_size_bound(true),
+            hybrid_candidates().with_thread_pool(pool.clone()).with_size_bound(true),
+        ];
+
+        for hybrid in hybrids.iter() {
+            assert_eq!(round_trip(hybrid, input), expected.len());
+            let mut compressed = Vec::new();
+            hybrid.compress(input, &mut compressed);
+            assert_eq!(compressed, expected);
+        }
+    }
+}
+
+#[test]
+fn test_compress_bounded() {
+    let input = (0..10_000u32).map(pseudo_random).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+
+    // gives up somewhere along the way instead of writing the whole output
+    assert!(!LZ::<u32>::new().compress_bounded(&input, &mut compressed, 1000));
+    assert!(compressed.len() < 2000);
+
+    compressed.clear();
+    assert!(!BitPack::<u32>::new().compress_bounded(&input, &mut compressed, 100));
+    assert!(compressed.len() < 1000);
+
+    compressed.clear();
+    assert!(!PFor::<u32>::new().compress_bounded(&input, &mut compressed, 100));
+    assert!(compressed.len() < 1000);
+
+    compressed.clear();
+    assert!(!NaiveCompressor::<u32>::new().compress_bounded(&input, &mut compressed, 100));
+    assert!(compressed.is_empty());
+
+    // too many distinct elements to fit, found before the window search even starts
+    compressed.clear();
+    assert!(!Lookup::<u32>::new().compress_bounded(&input, &mut compressed, 1000));
+    assert!(compressed.is_empty());
+
+    // and when everything fits, the output is the same as `compress`
+    let repeating = (0..10_000u32).map(|i| i % 10).collect::<Vec<_>>();
+    let mut expected = Vec::new();
+    PFor::<u32>::new().compress(&repeating, &mut expected);
+    compressed.clear();
+    assert!(PFor::<u32>::new().compress_bounded(&repeating, &mut compressed, expected.len()));
+    assert_eq!(compressed, expected);
+    compressed.clear();
+    assert!(Lookup::<u32>::new().compress_bounded(&repeating, &mut compressed, 100));
+    assert!(compressed.len() <= 100);
+
+    // a hybrid fails only if every algorithm does, and otherwise writes the same as `compress`
+    let hybrid = hybrid_candidates().with_size_bound(true);
+    let mut expected = Vec::new();
+    hybrid.compress(&input, &mut expected);
+
+    compressed.clear();
+    assert!(!hybrid.compress_bounded(&input, &mut compressed, expected.len() - 1));
+    compressed.clear();
+    assert!(hybrid.compress_bounded(&input, &mut compressed, expected.len()));
+    assert_eq!(compressed, expected);
+}
+
+#[test]
+fn test_hybrid_sampled_selection() {
+    let input = (0..100_000u32).map(|i| i / 3 + pseudo_random(i) % 4).collect::<Vec<_>>();
+
+    let mut expected = Vec::new();
+    let exhaustive = hybrid_candidates().compress_with_report(&input, &mut expected);
+    assert_eq!(exhaustive.confidence, 1.0);
+    assert!(exhaustive.exhaustive);
+
+    // the data looks the same everywhere, so the samples agree with compressing all of it
+    let sampled = hybrid_candidates().with_selection(Selection::sampled());
+    let mut compressed = Vec::new();
+    let report = sampled.compress_with_report(&input, &mut compressed);
+    assert_eq!(report.index, exhaustive.index);
+    assert_eq!(report.confidence, 1.0);
+    assert!(!report.exhaustive);
+    assert!((report.estimated_ratio - exhaustive.estimated_ratio).abs() < 0.05, "{report:?} vs {exhaustive:?}");
+    assert_eq!(compressed, expected);
+    round_trip(&sampled, &input);
+
+    // too short to sample
+    let short = &input[..1000];
+    let mut compressed = Vec::new();
+    let report = sampled.compress_with_report(short, &mut compressed);
+    assert!(report.exhaustive);
+    assert_eq!(report.confidence, 1.0);
+
+    // never confident enough, so every algorithm runs on all the data
+    let unsure = hybrid_candidates().with_selection(Selection::Sampled { samples: 4, sample_length: 256, min_confidence: 1.5 });
+    let mut compressed = Vec::new();
+    let report = unsure.compress_with_report(&input, &mut compressed);
+    assert!(report.exhaustive);
+    assert_eq!(compressed, expected);
+}
+
+#[test]
+fn test_hybrid_sampled_confidence() {
+    // first half is constant, second half noise: samples disagree on what's best
+    let input = (0..64_000u32).map(|i| if i < 32_000 { 7 } else { pseudo_random(i) }).collect::<Vec<_>>();
+    let sampled = hybrid_candidates().with_selection(Selection::Sampled { samples: 8, sample_length: 1000, min_confidence: 0.0 });
+
+    let mut compressed = Vec::new();
+    let report = sampled.compress_with_report(&input, &mut compressed);
+    assert!(report.confidence < 1.0 && report.confidence > 0.0, "{report:?}");
+    assert!(!report.exhaustive);
+
+    let mut decompressed = Vec::new();
+    sampled.decompress(&compressed, &mut decompressed);
+    assert_eq!(decompressed, input);
+}
+
+#[test]
+fn test_hybrid_objectives() {
+    // skewed bytes: entropy coders beat storing them as is, but take much longer to decode
+    let input = (0..200_000u32).map(|i| (pseudo_random(i) % 1000).trailing_zeros() as u8).collect::<Vec<_>>();
+    let candidates = || Hybrid::<u8>::new().add::<NaiveCompressor<u8>>().add::<Huffman<u8>>().add::<Arithmetic<u8>>();
+
+    let mut compressed = Vec::new();
+    let smallest = candidates().compress_with_report(&input, &mut compressed);
+    assert_ne!(smallest.index, 0);
+    assert_eq!(smallest.cost, (compressed.len() - 1) as f64);
+
+    // a millisecond of decoding is worth more than all the bytes saved
+    for selection in [Selection::Exhaustive, Selection::sampled()] {
+        let fast = candidates().with_selection(selection).with_objective(Objective::DecodeWeighted { bytes_per_millisecond: 1e9 });
+        let mut compressed = Vec::new();
+        assert_eq!(fast.compress_with_report(&input, &mut compressed).index, 0);
+        assert_eq!(round_trip(&fast, &input), input.len() + 1);
+    }
+
+    // custom costs see the size and both timings
+    let trials = std::sync::Arc::new(std::sync::Mutex::new(Vec::<Trial>::new()));
+    let seen = trials.clone();
+    let largest = candidates().with_objective(Objective::custom(move |trial| {
+        seen.lock().unwrap().push(*trial);
+        -(trial.size as f64)
+    }));
+
+    let mut compressed = Vec::new();
+    let report = largest.compress_with_report(&input, &mut compressed);
+    let trials = trials.lock().unwrap();
+    assert_eq!(trials.len(), 3);
+    assert!(trials.iter().all(|trial| trial.decode > std::time::Duration::ZERO));
+    assert_eq!(report.cost, -(trials.iter().map(|trial| trial.size).max().unwrap() as f64));
+}
+
+fn segmented_candidates() -> Segmented<u32> {
+    Segmented::<u32>::new()
+        .add::<NaiveCompressor<u32>>()
+        .add::<RLE<u32>>()
+        .add::<Delta<u32, RLE<u32>>>()
+}
+
+#[test]
+fn test_segmented_regimes() {
+    // a constant run, then noise, then a ramp
+    let constant = vec![4_206_767u32; 20_000];
+    let noise = (0..20_000).map(pseudo_random).collect::<Vec<_>>();
+    let ramp = (0..20_000u32).map(|i| 3 * i).collect::<Vec<_>>();
+    let input = [constant.clone(), noise.clone(), ramp.clone()].concat();
+
+    let segmented = segmented_candidates();
+    let segments = segmented.segments(&input);
+    assert_eq!(segments.iter().map(|(_, index)| *index).collect::<Vec<_>>(), vec![1, 0, 2]);
+    assert!(segments[1].0.start.abs_diff(20_000) <= 64, "{segments:?}");
+    assert!(segments[2].0.start.abs_diff(40_000) <= 64, "{segments:?}");
+
+    // as good as compressing every part with its best algorithm alone, plus the segment headers
+    let hybrid = Hybrid::<u32>::new().add::<NaiveCompressor<u32>>().add::<RLE<u32>>().add::<Delta<u32, RLE<u32>>>();
+    let parts = [&constant, &noise, &ramp].iter().map(|part| round_trip(&hybrid, part)).sum::<usize>();
+    let whole = round_trip(&hybrid, &input);
+    let size = round_trip(&segmented, &input);
+    // every boundary can be up to 64 raw elements off
+    assert!(size <= parts + 2 * 64 * 4 + 64, "segmented {size} vs parts {parts}");
+    assert!(size * 2 < whole, "segmented {size} vs whole {whole}");
+
+    let rebuilt = build::<u32>(&segmented.descriptor()).unwrap();
+    round_trip(&rebuilt, &input);
+    round_trip(&segmented.with_block_size(7), &input[..1000]);
+    round_trip(&segmented_candidates(), &[]);
+}
+
+#[test]
+fn test_segmented_invalid_input() {
+    let segmented = segmented_candidates().with_block_size(10);
+    let input = (0..100u32).map(|i| if i < 50 { 1 } else { pseudo_random(i) }).collect::<Vec<_>>();
+    let mut compressed = Vec::new();
+    segmented.compress(&input, &mut compressed);
+
+    let mut decompressed = Vec::new();
+    assert_eq!(segmented.try_decompress(&compressed[..compressed.len() - 1], &mut decompressed), Err(CompressionError::Truncated));
+
+    let mut trailing = compressed.clone();
+    trailing.push(0);
+    assert_eq!(segmented.try_decompress(&trailing, &mut Vec::new()), Err(CompressionError::TrailingBytes(1)));
+
+    // the first segment claims one element more than it holds
+    let mut miscounted = compressed.clone();
+    miscounted[4] += 1;
+    assert_eq!(segmented.try_decompress(&miscounted, &mut Vec::new()), Err(CompressionError::LengthMismatch { expected: 51, actual: 50 }));
+
+    let mut unknown = compressed;
+    unknown[2] = 9;
+    assert_eq!(segmented.try_decompress(&unknown, &mut Vec::new()), Err(CompressionError::UnknownAlgorithm(9)));
+}
+
+#[test]
+fn test_registry_names() {
+    for id in AlgorithmId::ALL {
+        assert_eq!(AlgorithmId::from_name(id.name()), Some(id));
+        let descriptor = Descriptor::node(id, id.default_children());
+        assert_eq!(descriptor.to_string(), id.name());
+        assert_eq!(id.name().parse::<Descriptor>(), Ok(descriptor));
+    }
+
+    // bare names stand for what `new()` builds
+    assert_eq!(RLE::<u8>::new().descriptor().to_string(), "rle");
+    assert_eq!(VRLE::<u32>::new().descriptor().to_string(), "vrle");
+    assert_eq!(Delta::<u32>::new().descriptor().to_string(), "delta");
+    assert_eq!(DeltaOfDelta::<u64>::new().descriptor().to_string(), "dod");
+    assert_eq!(Shuffle::<u16>::new().descriptor().to_string(), "shuffle");
+    assert_eq!(BitShuffle::<u16>::new().descriptor().to_string(), "bitshuffle");
+}
+
+#[test]
+fn test_registry_spec_round_trip() {
+    let spec = "parchunked(hybrid(rle,vrle,lookup))";
+    let expected = ParChunked::new_with(Hybrid::<u64>::new().add::<RLE<u64>>().add::<VRLE<u64>>().add::<Lookup<u64>>(), None);
+    let descriptor = spec.parse::<Descriptor>().unwrap();
+    assert_eq!(descriptor, expected.descriptor());
+    assert_eq!(descriptor.to_string(), spec);
+
+    let input = (0..10_000u64).map(|i| i / 10).collect::<Vec<_>>();
+    let compressor = Registry::new().build::<u64>(spec).unwrap();
+    assert_eq!(round_trip(&compressor, &input), round_trip(&expected, &input));
+
+    // whitespace is ignored and default inner algorithms are left out when printing
+    let descriptor = " hybrid ( rle(naive, naive) , delta( bitpack ), rle() )".parse::<Descriptor>().unwrap();
+    assert_eq!(descriptor.to_string(), "hybrid(rle,delta(bitpack),rle())");
+    assert_eq!(descriptor.children[2], Descriptor::leaf(AlgorithmId::RLE));
+}
+
+#[test]
+fn test_registry_aliases() {
+    let registry = Registry::new()
+        .with_alias("fast", "hybrid(rle,lz)").unwrap()
+        .with_alias("delta", "delta(vrle)").unwrap();
+    assert_eq!(registry.aliases().collect::<Vec<_>>(), vec!["fast", "delta"]);
+
+    let descriptor = registry.parse("parchunked(fast)").unwrap();
+    assert_eq!(descriptor.to_string(), "parchunked(hybrid(rle,lz))");
+    assert_eq!(registry.print(&descriptor), "parchunked(fast)");
+
+    // a bare alias shadows the algorithm, but parentheses always mean the algorithm
+    assert_eq!(registry.parse("delta").unwrap().to_string(), "delta(vrle)");
+    let plain_delta = registry.parse("delta(naive)").unwrap();
+    assert_eq!(registry.print(&plain_delta), "delta(naive)");
+    assert_eq!(registry.parse(&registry.print(&plain_delta)).unwrap(), plain_delta);
+
+    assert!(Registry::new().with_alias("no spaces", "rle").is_err());
+    assert!(registry.clone().with_alias("broken", "fast(rle)").is_err());
+}
+
+#[test]
+fn test_registry_invalid_specs() {
+    let error = |position, reason| Err(CompressionError::InvalidSpec { position, reason });
+
+    assert_eq!("".parse::<Descriptor>(), error(0, "expected an algorithm name"));
+    assert_eq!("zstd".parse::<Descriptor>(), error(0, "unknown algorithm name"));
+    assert_eq!("hybrid(rle, zstd)".parse::<Descriptor>(), error(12, "unknown algorithm name"));
+    assert_eq!("hybrid(rle".parse::<Descriptor>(), error(10, "expected ',' or ')'"));
+    assert_eq!("hybrid(rle,".parse::<Descriptor>(), error(11, "expected an algorithm name"));
+    assert_eq!("rle,vrle".parse::<Descriptor>(), error(3, "unexpected characters after the spec"));
+    assert_eq!("rle)".parse::<Descriptor>(), error(3, "unexpected characters after the spec"));
+
+    let deep = "delta(".repeat(40) + &")".repeat(40);
+    assert!(matches!(deep.parse::<Descriptor>(), Err(CompressionError::InvalidSpec { reason: "algorithm tree is too deep", .. })));
+
+    // parses, but can't be built
+    assert_eq!(Registry::new().build::<u8>("hybrid()").err(), Some(CompressionError::InvalidHeader("no inner algorithms")));
+    assert_eq!(Registry::new().build::<u8>("rle(naive)").err(), Some(CompressionError::InvalidHeader("wrong number of inner algorithms")));
+}